@@ -1,137 +1,266 @@
 //遍历内存形式的IR,解析得到汇编代码
 
-use koopa::ir::{BinaryOp, FunctionData, Value, ValueKind};
+use koopa::ir::{BinaryOp, FunctionData, TypeKind, Value, ValueKind};
 
-use crate::asm_builder::REGISTER_NAMES;
+use crate::asm_builder::ARG_REGISTERS;
+use crate::error::{CompileError, Span};
 
-use super::GenerateAsmInfo;
-use std::io::Write;
+use super::{GenerateAsmInfo, StackFrame};
+use std::collections::HashMap;
 // 根据内存形式 Koopa IR 生成汇编
 pub trait GenerateAsm {
-    fn generate(&self, asm_info: &mut GenerateAsmInfo) -> Result<(), String>;
+    fn generate(&self, asm_info: &mut GenerateAsmInfo) -> Result<(), CompileError>;
 }
 
 impl GenerateAsm for koopa::ir::Program {
-    fn generate(&self, asm_info: &mut GenerateAsmInfo) -> Result<(), String> {
-        let mut asm_codes = Vec::<String>::new();
-        asm_codes.push(String::from("  .text"));
+    fn generate(&self, asm_info: &mut GenerateAsmInfo) -> Result<(), CompileError> {
+        //记录每个函数的汇编标签, 生成 call 时使用
+        for &func in self.func_layout() {
+            asm_info
+                .func_names
+                .insert(func, self.func(func).name()[1..].to_string());
+        }
+        //生成全局变量: 有初始值的放在 .data 段, 初始化为 0 的放在 .bss 段
+        for &global in self.inst_layout() {
+            let global_data = self.borrow_value(global);
+            let name = global_data.name().as_ref().unwrap()[1..].to_string();
+            let init = match global_data.kind() {
+                ValueKind::GlobalAlloc(global_alloc) => global_alloc.init(),
+                _ => unreachable!(),
+            };
+            let init_data = self.borrow_value(init);
+            if let ValueKind::ZeroInit(_) = init_data.kind() {
+                writeln!(asm_info.output_file, "  .bss")?;
+            } else {
+                writeln!(asm_info.output_file, "  .data")?;
+            }
+            writeln!(asm_info.output_file, "  .global {}", name)?;
+            writeln!(asm_info.output_file, "{}:", name)?;
+            generate_global_init(self, init, asm_info)?;
+            writeln!(asm_info.output_file)?;
+            asm_info.global_names.insert(global, name);
+            asm_info.global_types.insert(global, global_data.ty().clone());
+        }
+        writeln!(asm_info.output_file, "  .text")?;
         //生成函数的汇编代码
         for &func in self.func_layout() {
+            //运行时库的函数只有声明, 由链接器从 libsysy 中找到
+            if self.func(func).layout().entry_bb().is_none() {
+                continue;
+            }
             self.func(func).generate(asm_info)?;
         }
         Ok(())
     }
 }
 
+/// 按顺序输出全局变量的初始值
+fn generate_global_init(
+    program: &koopa::ir::Program,
+    init: Value,
+    asm_info: &mut GenerateAsmInfo,
+) -> Result<(), CompileError> {
+    let init_data = program.borrow_value(init);
+    match init_data.kind() {
+        ValueKind::Integer(int) => {
+            writeln!(asm_info.output_file, "  .word {}", int.value())?;
+        }
+        ValueKind::ZeroInit(_) => {
+            writeln!(asm_info.output_file, "  .zero {}", init_data.ty().size())?;
+        }
+        ValueKind::Aggregate(aggregate) => {
+            for &elem in aggregate.elems() {
+                generate_global_init(program, elem, asm_info)?;
+            }
+        }
+        value_kind => {
+            //后端只能看到 Koopa IR, 没有对应的源代码位置
+            return Err(CompileError::Codegen(
+                format!("Unsupported global initializer: {:?}", value_kind),
+                Span::default(),
+            ))
+        }
+    }
+    Ok(())
+}
+
 impl GenerateAsm for koopa::ir::FunctionData {
-    fn generate(&self, asm_info: &mut GenerateAsmInfo) -> Result<(), String> {
-        writeln!(asm_info.output_file, "  .global {}", &self.name()[1..]).expect("Write error. ");
-        writeln!(asm_info.output_file, "{}:", &self.name()[1..]).expect("Write error. ");
-        for (&bb, node) in self.layout().bbs() {
+    fn generate(&self, asm_info: &mut GenerateAsmInfo) -> Result<(), CompileError> {
+        asm_info.stack_frame = build_stack_frame(self);
+        writeln!(asm_info.output_file, "  .global {}", &self.name()[1..])?;
+        writeln!(asm_info.output_file, "{}:", &self.name()[1..])?;
+        //prologue: 开辟栈帧, 保存 ra, 并把寄存器传入的参数保存到栈上
+        let frame_size = asm_info.stack_frame.size as i32;
+        if frame_size > 0 {
+            add_sp(-frame_size, asm_info)?;
+        }
+        if asm_info.stack_frame.save_ra {
+            access_stack("sw", "ra", asm_info.stack_frame.size - 4, asm_info)?;
+        }
+        for (i, &param) in self.params().iter().enumerate().take(ARG_REGISTERS.len()) {
+            store_reg_to_value(param, ARG_REGISTERS[i], asm_info)?;
+        }
+        //为每个基本块分配汇编标签
+        let mut bb_labels = HashMap::new();
+        for (i, &bb) in self.layout().bbs().keys().enumerate() {
+            let bb_name = match self.dfg().bb(bb).name() {
+                Some(name) => name[1..].to_string(),
+                None => format!("bb{}", i),
+            };
+            bb_labels.insert(bb, format!(".L{}_{}", &self.name()[1..], bb_name));
+        }
+        for (bb, node) in self.layout().bbs() {
             //遍历基本块列表
+            writeln!(asm_info.output_file, "{}:", bb_labels[bb])?;
             for &inst in node.insts().keys() {
                 //访问指令列表
                 //访问指令
                 let value_data = self.dfg().value(inst);
                 match value_data.kind() {
-                    ValueKind::Integer(int) => {
+                    ValueKind::Integer(_) => {
                         //处理int指令
                         todo!()
                     }
                     ValueKind::Return(ret) => {
                         //处理return
-                        match ret.value() {
-                            Some(ret) => {
-                                let val = self.dfg().value(ret);
-                                match val.kind() {
-                                    ValueKind::Integer(int) => {
-                                        writeln!(asm_info.output_file, "  li a0, {}", int.value())
-                                            .expect("Write error. ");
-                                    }
-                                    ValueKind::Binary(binary)=>{
-                                        writeln!(asm_info.output_file, "  mv a0, {}", REGISTER_NAMES[asm_info.find_using_register(ret).expect("No register for return value. Should never happen! ")])
-                                            .expect("Write error. ");
-                                    }
-                                    _ => unreachable!(),
-                                }
-                            }
-                            None => {}
+                        if let Some(ret) = ret.value() {
+                            load_value_to_reg(self, ret, "a0", asm_info)?;
+                        }
+                        //epilogue: 恢复 ra, 回收栈帧
+                        if asm_info.stack_frame.save_ra {
+                            access_stack("lw", "ra", asm_info.stack_frame.size - 4, asm_info)?;
                         }
-                        writeln!(asm_info.output_file, "  ret").expect("Write error. ");
+                        if frame_size > 0 {
+                            add_sp(frame_size, asm_info)?;
+                        }
+                        writeln!(asm_info.output_file, "  ret")?;
                     }
                     ValueKind::Binary(binary) => {
-                        let reg1 = get_reg(self, binary.lhs(), asm_info)?;
-                        let reg2 = get_reg(self, binary.rhs(), asm_info)?;
-                        asm_info.free_register(reg1);
-                        asm_info.free_register(reg2);  //让结果可以复用上面的寄存器
-                        let reg_ans=asm_info.allocate_register(inst); //为指令的返回值分配寄存器   为什么不用get_reg?
+                        load_value_to_reg(self, binary.lhs(), "t0", asm_info)?;
+                        load_value_to_reg(self, binary.rhs(), "t1", asm_info)?;
                         match binary.op() {
                             BinaryOp::NotEq => {
-                                writeln!(asm_info.output_file,"  xor   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
-                                writeln!(asm_info.output_file,"  snez  {},{}", REGISTER_NAMES[reg_ans],REGISTER_NAMES[reg_ans]).expect("Write error. ");
+                                writeln!(asm_info.output_file,"  xor   t0,t0,t1")?;
+                                writeln!(asm_info.output_file,"  snez  t0,t0")?;
                             },
                             BinaryOp::Eq => {
-                                //你也许会注意到, 如果按照一条指令的结果占用一个临时寄存器的目标代码生成思路, 在表达式足够复杂的情况下,
-                                //所有的临时寄存器很快就会被用完. 本章出现的测试用例中会避免出现这种情况,
-                                //同时, 你可以自行思考: 用何种方式可以缓解这个问题. 在 Lv4 中, 我们会给出一种一劳永逸的思路来解决这个问题.
-                                writeln!(asm_info.output_file,"  xor   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
-                                writeln!(asm_info.output_file,"  seqz  {},{}", REGISTER_NAMES[reg_ans],REGISTER_NAMES[reg_ans]).expect("Write error. ");
+                                writeln!(asm_info.output_file,"  xor   t0,t0,t1")?;
+                                writeln!(asm_info.output_file,"  seqz  t0,t0")?;
                             }
                             BinaryOp::Gt => {
                                 //sgt是一个伪指令,也就是说, 这条指令并不真实存在, 而是用其他指令实现的.
                                 //sgt t0, t1, t2 (判断 t1 的值是否大于 t2 的值) 是怎么实现的?
                                 // = slt t0,t2,t1
-                                
-                                writeln!(asm_info.output_file,"  sgt   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
-                                writeln!(asm_info.output_file,"  snez   {},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg_ans]).expect("Write error. ");
-                                
+                                writeln!(asm_info.output_file,"  sgt   t0,t0,t1")?;
                             },
                             BinaryOp::Lt => {
-                                //slt t0, t1, t2 指令的含义是, 判断寄存器 t1 的值是否小于 t2 的值, 并将结果 (0 或 1) 写入 t0 寄存器. 
-                                writeln!(asm_info.output_file,"  slt   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
-                                writeln!(asm_info.output_file,"  snez   {},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg_ans]).expect("Write error. ");
-                                
+                                //slt t0, t1, t2 指令的含义是, 判断寄存器 t1 的值是否小于 t2 的值, 并将结果 (0 或 1) 写入 t0 寄存器.
+                                writeln!(asm_info.output_file,"  slt   t0,t0,t1")?;
                             },
                             BinaryOp::Ge => {
                                 //判断大于等于的原理是什么? => 判断是否小于后面，取反
-                                writeln!(asm_info.output_file,"  slt   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
-                                writeln!(asm_info.output_file,"  seqz   {},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg_ans]).expect("Write error. ");
+                                writeln!(asm_info.output_file,"  slt   t0,t0,t1")?;
+                                writeln!(asm_info.output_file,"  seqz  t0,t0")?;
                             },
                             BinaryOp::Le => {
                                 //判断小于等于的原理是什么? => 判断是否大于后面，取反
-                                writeln!(asm_info.output_file,"  sgt   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
-                                writeln!(asm_info.output_file,"  seqz   {},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg_ans]).expect("Write error. ");
-
+                                writeln!(asm_info.output_file,"  sgt   t0,t0,t1")?;
+                                writeln!(asm_info.output_file,"  seqz  t0,t0")?;
                             },
                             BinaryOp::Add => {
-                                writeln!(asm_info.output_file,"  add   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
+                                writeln!(asm_info.output_file,"  add   t0,t0,t1")?;
                             },
                             BinaryOp::Sub => {
-                                writeln!(asm_info.output_file,"  sub   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
+                                writeln!(asm_info.output_file,"  sub   t0,t0,t1")?;
                             },
                             BinaryOp::Mul => {
-                                writeln!(asm_info.output_file,"  mul   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
-                                
+                                writeln!(asm_info.output_file,"  mul   t0,t0,t1")?;
                             },
                             BinaryOp::Div => {
-                                writeln!(asm_info.output_file,"  div   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
+                                writeln!(asm_info.output_file,"  div   t0,t0,t1")?;
                             },
                             BinaryOp::Mod => {
-                                writeln!(asm_info.output_file,"  rem   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
+                                writeln!(asm_info.output_file,"  rem   t0,t0,t1")?;
                             },
                             BinaryOp::And => {
-                                writeln!(asm_info.output_file,"  and   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
-                                
+                                writeln!(asm_info.output_file,"  and   t0,t0,t1")?;
                             },
                             BinaryOp::Or => {
-                                writeln!(asm_info.output_file,"  or   {},{},{}",REGISTER_NAMES[reg_ans], REGISTER_NAMES[reg1],REGISTER_NAMES[reg2]).expect("Write error. ");
-                                
+                                writeln!(asm_info.output_file,"  or    t0,t0,t1")?;
                             },
                             BinaryOp::Xor => todo!(),
                             BinaryOp::Shl => todo!(),
                             BinaryOp::Shr => todo!(),
                             BinaryOp::Sar => todo!(),
                         }
+                        //每条指令的结果都保存到栈上
+                        store_reg_to_value(inst, "t0", asm_info)?;
+                    }
+                    ValueKind::Branch(branch) => {
+                        //条件不为 0 时跳转到 true 分支, 否则跳转到 false 分支
+                        load_value_to_reg(self, branch.cond(), "t0", asm_info)?;
+                        writeln!(asm_info.output_file, "  bnez  t0, {}", bb_labels[&branch.true_bb()])?;
+                        writeln!(asm_info.output_file, "  j     {}", bb_labels[&branch.false_bb()])?;
+                    }
+                    ValueKind::Jump(jump) => {
+                        writeln!(asm_info.output_file, "  j     {}", bb_labels[&jump.target()])?;
+                    }
+                    ValueKind::Call(call) => {
+                        //前 8 个参数放在 a0-a7 中, 其余参数从 sp 开始依次放在栈上
+                        for (i, &arg) in call.args().iter().enumerate() {
+                            if i < ARG_REGISTERS.len() {
+                                load_value_to_reg(self, arg, ARG_REGISTERS[i], asm_info)?;
+                            } else {
+                                load_value_to_reg(self, arg, "t0", asm_info)?;
+                                access_stack("sw", "t0", (i - ARG_REGISTERS.len()) * 4, asm_info)?;
+                            }
+                        }
+                        let callee = asm_info.func_names[&call.callee()].clone();
+                        writeln!(asm_info.output_file, "  call  {}", callee)?;
+                        //返回值在 a0 中
+                        if !value_data.ty().is_unit() {
+                            store_reg_to_value(inst, "a0", asm_info)?;
+                        }
+                    }
+                    ValueKind::Alloc(_) => {
+                        //局部变量的空间已经在栈帧中分配好了
+                    }
+                    ValueKind::Load(load) => {
+                        if is_local_alloc(self, load.src()) {
+                            let offset = asm_info.stack_frame.offset(load.src()).unwrap();
+                            access_stack("lw", "t0", offset, asm_info)?;
+                        } else {
+                            //通过指针读取
+                            load_value_to_reg(self, load.src(), "t0", asm_info)?;
+                            writeln!(asm_info.output_file, "  lw    t0, 0(t0)")?;
+                        }
+                        store_reg_to_value(inst, "t0", asm_info)?;
+                    }
+                    ValueKind::Store(store) => {
+                        load_value_to_reg(self, store.value(), "t0", asm_info)?;
+                        if is_local_alloc(self, store.dest()) {
+                            let offset = asm_info.stack_frame.offset(store.dest()).unwrap();
+                            access_stack("sw", "t0", offset, asm_info)?;
+                        } else {
+                            //通过指针写入
+                            load_value_to_reg(self, store.dest(), "t1", asm_info)?;
+                            writeln!(asm_info.output_file, "  sw    t0, 0(t1)")?;
+                        }
+                    }
+                    ValueKind::GetElemPtr(get_elem_ptr) => {
+                        //src 指向数组, 结果为 src + index * 元素大小
+                        let elem_size = match pointer_base(self, get_elem_ptr.src(), asm_info).kind() {
+                            TypeKind::Array(base, _) => base.size(),
+                            _ => unreachable!(),
+                        };
+                        generate_ptr_offset(self, get_elem_ptr.src(), get_elem_ptr.index(), elem_size, asm_info)?;
+                        store_reg_to_value(inst, "t0", asm_info)?;
+                    }
+                    ValueKind::GetPtr(get_ptr) => {
+                        //src 指向数组中的某个元素, 结果为 src + index * 元素大小
+                        let elem_size = pointer_base(self, get_ptr.src(), asm_info).size();
+                        generate_ptr_offset(self, get_ptr.src(), get_ptr.index(), elem_size, asm_info)?;
+                        store_reg_to_value(inst, "t0", asm_info)?;
                     }
                     // 其他种类暂时遇不到
                     _ => unreachable!(),
@@ -142,37 +271,171 @@ impl GenerateAsm for koopa::ir::FunctionData {
     }
 }
 
+/// 计算函数的栈帧: 为局部变量, 每条有返回值的指令, 以及超出 8 个的调用参数分配空间.
+fn build_stack_frame(fd: &FunctionData) -> StackFrame {
+    let mut save_ra = false;
+    let mut max_call_args = 0;
+    for (_, node) in fd.layout().bbs() {
+        for &inst in node.insts().keys() {
+            if let ValueKind::Call(call) = fd.dfg().value(inst).kind() {
+                save_ra = true;
+                max_call_args = max_call_args.max(call.args().len());
+            }
+        }
+    }
+    //栈帧底部留给被调函数的参数
+    let mut offset = max_call_args.saturating_sub(ARG_REGISTERS.len()) * 4;
+    let mut offsets = HashMap::new();
+    //寄存器传入的参数也保存到栈上, 避免被后续的 call 覆盖
+    for &param in fd.params().iter().take(ARG_REGISTERS.len()) {
+        offsets.insert(param, offset);
+        offset += 4;
+    }
+    for (_, node) in fd.layout().bbs() {
+        for &inst in node.insts().keys() {
+            let value_data = fd.dfg().value(inst);
+            let size = match value_data.kind() {
+                //alloc 需要为指向的对象分配空间
+                ValueKind::Alloc(_) => match value_data.ty().kind() {
+                    TypeKind::Pointer(base) => base.size(),
+                    _ => unreachable!(),
+                },
+                _ => value_data.ty().size(),
+            };
+            if size > 0 {
+                offsets.insert(inst, offset);
+                offset += size;
+            }
+        }
+    }
+    if save_ra {
+        offset += 4;
+    }
+    StackFrame {
+        //按 16 字节对齐
+        size: offset.div_ceil(16) * 16,
+        save_ra,
+        offsets,
+    }
+}
+
+/// 指针指向的类型
+fn pointer_base(fd: &FunctionData, ptr: Value, asm_info: &GenerateAsmInfo) -> koopa::ir::Type {
+    let ty = if ptr.is_global() {
+        asm_info.global_types[&ptr].clone()
+    } else {
+        fd.dfg().value(ptr).ty().clone()
+    };
+    match ty.kind() {
+        TypeKind::Pointer(base) => base.clone(),
+        _ => unreachable!(),
+    }
+}
 
-/// -.-
-/// Given a component of a binary op (e.g. an Integer or answer of another binary op),
-/// get its string representation in assembly code.
+/// 计算 t0 = ptr + index * elem_size
+fn generate_ptr_offset(
+    fd: &FunctionData,
+    ptr: Value,
+    index: Value,
+    elem_size: usize,
+    asm_info: &mut GenerateAsmInfo,
+) -> Result<(), CompileError> {
+    load_value_to_reg(fd, ptr, "t0", asm_info)?;
+    if let ValueKind::Integer(int) = fd.dfg().value(index).kind() {
+        //下标是常量时直接算出偏移
+        let offset = int.value() * elem_size as i32;
+        if offset == 0 {
+            return Ok(());
+        }
+        writeln!(asm_info.output_file, "  li    t1, {}", offset)?;
+    } else {
+        load_value_to_reg(fd, index, "t1", asm_info)?;
+        writeln!(asm_info.output_file, "  li    t2, {}", elem_size)?;
+        writeln!(asm_info.output_file, "  mul   t1, t1, t2")?;
+    }
+    writeln!(asm_info.output_file, "  add   t0, t0, t1")?;
+    Ok(())
+}
+
+/// 判断一个指针是否是当前函数中的局部变量 (alloc)
+fn is_local_alloc(fd: &FunctionData, ptr: Value) -> bool {
+    !ptr.is_global() && matches!(fd.dfg().value(ptr).kind(), ValueKind::Alloc(_))
+}
+
+/// 调整 sp, 立即数超出 12 位时借助 t0
+fn add_sp(delta: i32, asm_info: &mut GenerateAsmInfo) -> Result<(), CompileError> {
+    if (-2048..2048).contains(&delta) {
+        writeln!(asm_info.output_file, "  addi  sp, sp, {}", delta)?;
+    } else {
+        writeln!(asm_info.output_file, "  li    t0, {}", delta)?;
+        writeln!(asm_info.output_file, "  add   sp, sp, t0")?;
+    }
+    Ok(())
+}
+
+/// 把一个值加载到寄存器 reg 中.
 ///
-/// e.g. %1 = sub 0, %0, the lhs is converted into "0",
-/// and the rhs is converted into "t0" (its register).
-fn get_reg(
+/// 整数直接用 li 加载, 全局变量与局部变量加载的是地址,
+/// 第 9 个及之后的参数位于调用者的栈帧中, 其余的值都保存在当前栈帧里.
+fn load_value_to_reg(
     fd: &FunctionData,
     value: Value,
+    reg: &str,
     asm_info: &mut GenerateAsmInfo,
-) -> Result<usize, String> {
+) -> Result<(), CompileError> {
+    if value.is_global() {
+        //全局变量的值是它的地址
+        writeln!(asm_info.output_file, "  la    {}, {}", reg, asm_info.global_names[&value])?;
+        return Ok(());
+    }
     match fd.dfg().value(value).kind() {
         ValueKind::Integer(int) => {
-            // Allocate a new register for the Integer.
-            if int.value()==0 {
-                Ok(0) // Register x0(id=0) is always 0. 
-            }
-            else {
-                let reg = asm_info.allocate_register(value);
-                writeln!(asm_info.output_file, "  li   {}, {}", REGISTER_NAMES[reg], int.value()).expect("Write error. ");
-                Ok(reg)
+            writeln!(asm_info.output_file, "  li    {}, {}", reg, int.value())?;
+        }
+        ValueKind::FuncArgRef(arg) if arg.index() >= ARG_REGISTERS.len() => {
+            let offset = asm_info.stack_frame.size + (arg.index() - ARG_REGISTERS.len()) * 4;
+            access_stack("lw", reg, offset, asm_info)?;
+        }
+        ValueKind::Alloc(_) => {
+            //alloc 的值是局部变量的地址
+            let offset = asm_info.stack_frame.offset(value).unwrap();
+            if offset < 2048 {
+                writeln!(asm_info.output_file, "  addi  {}, sp, {}", reg, offset)?;
+            } else {
+                writeln!(asm_info.output_file, "  li    t3, {}", offset)?;
+                writeln!(asm_info.output_file, "  add   {}, sp, t3", reg)?;
             }
         }
-        ValueKind::Binary(_) => {
-            // Use the register allocated for this expression.
-            let reg = asm_info.find_using_register(value).expect(
-                    "No register for expression. This should not happen. \n",
-            );
-            Ok(reg)
+        value_kind => {
+            let offset = asm_info
+                .stack_frame
+                .offset(value)
+                .ok_or_else(|| {
+                    CompileError::Codegen(format!("Value has no stack slot: {:?}", value_kind), Span::default())
+                })?;
+            access_stack("lw", reg, offset, asm_info)?;
         }
-        value_kind => Err(format!("Wrong type in LHS: {:?}", value_kind)),
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+/// 把寄存器 reg 的值保存到 value 在栈帧中的位置
+fn store_reg_to_value(value: Value, reg: &str, asm_info: &mut GenerateAsmInfo) -> Result<(), CompileError> {
+    let offset = asm_info
+        .stack_frame
+        .offset(value)
+        .ok_or_else(|| CompileError::Codegen("Value has no stack slot".to_string(), Span::default()))?;
+    access_stack("sw", reg, offset, asm_info)
+}
+
+/// 读写 sp + offset 处的内存, 偏移超出 12 位立即数的范围时借助 t3 计算地址
+fn access_stack(op: &str, reg: &str, offset: usize, asm_info: &mut GenerateAsmInfo) -> Result<(), CompileError> {
+    if offset < 2048 {
+        writeln!(asm_info.output_file, "  {:<5} {}, {}(sp)", op, reg, offset)?;
+    } else {
+        writeln!(asm_info.output_file, "  li    t3, {}", offset)?;
+        writeln!(asm_info.output_file, "  add   t3, sp, t3")?;
+        writeln!(asm_info.output_file, "  {:<5} {}, 0(t3)", op, reg)?;
+    }
+    Ok(())
+}
@@ -1,60 +1,54 @@
-use std::fs::File;
+use std::collections::HashMap;
+use std::io::Write;
 
-use koopa::ir::{Program, Value};
+use koopa::ir::{Function, Program, Type, Value};
 
+use crate::error::CompileError;
 
+
+#[allow(clippy::module_inception)]
 mod asm_builder;
 use asm_builder::GenerateAsm;
-//寄存器列表
-const REGISTER_NAMES: [&str; 32] = [
-    "x0", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "fp", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
-    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5",
-    "t6",
-];
-//可用于临时寄存器的列表
-const REGISTER_FOR_TEMP: [usize; 15] = [5, 6, 7, 28, 29, 30, 31, 10, 11, 12, 13, 14, 15, 16, 17];
-pub fn generate_riscv_asm(mem_ir:&Program,output_file:File)->Result<(), String>{
+//前 8 个参数通过寄存器传递
+const ARG_REGISTERS: [&str; 8] = ["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+/// 生成 RISC-V 汇编, 写入 output (文件或内存中的缓冲区)
+pub fn generate_riscv_asm(mem_ir:&Program,mut output:impl Write)->Result<(), CompileError>{
+    //RV32 中指针占 4 字节
+    Type::set_ptr_size(4);
     let mut asm_info=GenerateAsmInfo{
-        register_user:[None;32],
-        output_file,
+        output_file:&mut output,
+        func_names:HashMap::new(),
+        global_names:HashMap::new(),
+        global_types:HashMap::new(),
+        stack_frame:StackFrame::default(),
     };
     mem_ir.generate(&mut asm_info)?;
     Ok(())
 }
-pub struct GenerateAsmInfo{
-    register_user:[Option<Value>;32],
-    output_file: File,
+pub struct GenerateAsmInfo<'a>{
+    output_file: &'a mut dyn Write,
+    func_names:HashMap<Function,String>, //函数对应的汇编标签
+    global_names:HashMap<Value,String>,  //全局变量对应的汇编标签
+    global_types:HashMap<Value,Type>,    //全局变量的类型 (指针)
+    stack_frame:StackFrame,              //当前函数的栈帧
 }
-impl GenerateAsmInfo {
-    //寻找一个可用的寄存器
-    fn get_available_register(&self)->usize{
-        for i in REGISTER_FOR_TEMP{
-            match self.register_user[i] {
-                None=>return i,
-                Some(_) => continue,
-            }
-        }
-        panic!();  //寄存器用完了
-    }
-    //通过Value寻找寄存器
-    fn find_using_register(&self,value:Value)->Option<usize>{
-        for i in 0..32{
-            if let Some(v)=self.register_user[i]{
-                if v==value{
-                    return Some(i);
-                }
-            }
-        }
-        None
-    }
-    //为寄存器分配使用者Value
-    fn allocate_register(&mut self,value:Value)->usize{
-        let reg=self.get_available_register();
-        self.register_user[reg]=Some(value);
-        reg
-    }
-    //释放寄存器
-    fn free_register(&mut self,reg_id:usize){
-        self.register_user[reg_id]=None;
+
+/// 函数的栈帧布局 (从高地址到低地址):
+///
+/// ```text
+/// sp + size - 4      保存的 ra (仅当函数中有 call 时)
+///        ...         局部变量 (alloc) 与指令结果
+/// sp + 0             传给被调函数的第 9 个及之后的参数
+/// ```
+#[derive(Default)]
+pub struct StackFrame {
+    size: usize,                   //栈帧大小, 按 16 字节对齐
+    save_ra: bool,                 //是否需要保存 ra
+    offsets: HashMap<Value, usize>, //每个值在栈帧中相对 sp 的偏移
+}
+impl StackFrame {
+    //查询值在栈上的位置
+    fn offset(&self, value: Value) -> Option<usize> {
+        self.offsets.get(&value).copied()
     }
-}
\ No newline at end of file
+}
@@ -1,25 +1,38 @@
 //! Definition Exp of the Abstract Syntax Tree (AST). 
 //! Currently, AST is defined as follows:
 //!
-//! CompUnit  ::= FuncDef;
-//! FuncDef   ::= FuncType Id "(" ")" Block;
-//! FuncType  ::= "int";
-//! Decl          ::= ConstDecl;
+//! CompUnit  ::= (Decl | FuncDef) {Decl | FuncDef};
+//! FuncDef   ::= FuncType Id "(" [FuncFParams] ")" Block;
+//! FuncType  ::= "void" | "int";
+//! FuncFParams   ::= FuncFParam {"," FuncFParam};
+//! FuncFParam    ::= BType IDENT ["[" "]" {"[" ConstExp "]"}];
+//! Decl          ::= ConstDecl | VarDecl;
 //! ConstDecl     ::= "const" BType ConstDef {"," ConstDef} ";";
 //! BType         ::= "int";
-//! ConstDef      ::= IDENT "=" ConstInitVal;
-//! ConstInitVal  ::= ConstExp;
+//! ConstDef      ::= IDENT {"[" ConstExp "]"} "=" ConstInitVal;
+//! ConstInitVal  ::= ConstExp | "{" [ConstInitVal {"," ConstInitVal}] "}";
+//! VarDecl       ::= BType VarDef {"," VarDef} ";";
+//! VarDef        ::= IDENT {"[" ConstExp "]"}
+//!                 | IDENT {"[" ConstExp "]"} "=" InitVal;
+//! InitVal       ::= Exp | "{" [InitVal {"," InitVal}] "}";
 //! Block         ::= "{" {BlockItem} "}";
 //! BlockItem     ::= Decl | Stmt;
-//! LVal          ::= IDENT;
+//! LVal          ::= IDENT {"[" Exp "]"};
 //! ConstExp      ::= Exp;
-//! Stmt        ::= "return" Exp ";";
+//! Stmt        ::= "return" [Exp] ";"
 //!                 | LVal "=" Exp ";"
+//!                 | [Exp] ";"
+//!                 | Block
+//!                 | "if" "(" Exp ")" Stmt ["else" Stmt]
+//!                 | "while" "(" Exp ")" Stmt
+//!                 | "break" ";"
+//!                 | "continue" ";";
 //! This file include:
 //! Exp         ::= LOrExp;
 //! PrimaryExp  ::= "(" Exp ")" | Number | LVal;
 //! Number      ::= INT_CONST;
-//! UnaryExp    ::= PrimaryExp | UnaryOp UnaryExp;
+//! UnaryExp    ::= PrimaryExp | IDENT "(" [FuncRParams] ")" | UnaryOp UnaryExp;
+//! FuncRParams ::= Exp {"," Exp};
 //! UnaryOp     ::= "+" | "-" | "!";
 //! MulExp      ::= UnaryExp | MulExp ("*" | "/" | "%") UnaryExp;
 //! AddExp      ::= MulExp | AddExp ("+" | "-") MulExp;
@@ -28,61 +41,95 @@
 //! LAndExp     ::= EqExp | LAndExp "&&" EqExp;
 //! LOrExp      ::= LAndExp | LOrExp "||" LAndExp;
 
+//! 上面按优先级分层的表达式在解析时都归约成扁平的 Expr 树, 优先级体现在树的结构中.
+
+use std::fmt;
+
 use super::statements::*;
+use crate::error::Span;
 
+/// 表达式, 每个节点都记录它在源代码中的区间 (Span)
 #[derive(Debug)]
-pub enum Exp{
-    LOrExp(LOrExp)
+pub enum Expr {
+    Binary(BinaryOp, Box<Expr>, Box<Expr>, Span),
+    Unary(UnaryOp, Box<Expr>, Span),
+    Call(IDENT, Vec<Expr>, Span),  //函数调用
+    Index(IDENT, Vec<Expr>, Span), //数组元素 a[i][j]
+    Var(IDENT),
+    Lit(i32, Span), //整数字面量
 }
-#[derive(Debug)]
-pub enum UnaryExp{
-    //一元表达式
-    PrimaryExp(PrimaryExp),
-    PlusUnaryExp(Box<UnaryExp>), 
-    MinusUnaryExp(Box<UnaryExp>), 
-    NotUnaryExp(Box<UnaryExp>), 
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary(_, _, _, span)
+            | Expr::Unary(_, _, span)
+            | Expr::Call(_, _, span)
+            | Expr::Index(_, _, span)
+            | Expr::Lit(_, span) => *span,
+            Expr::Var(ident) => ident.span,
+        }
+    }
 }
-#[derive(Debug)]
-pub enum PrimaryExp{
-    BracedExp(Box<Exp>),
-    Number(Number),
-    LVal(LVal),
+//作为右值使用的左值
+impl From<LVal> for Expr {
+    fn from(lval: LVal) -> Self {
+        match lval {
+            LVal::IDENT(ident) => Expr::Var(ident),
+            LVal::ArrayElem(ident, indices, span) => Expr::Index(ident, indices, span),
+        }
+    }
 }
-#[derive(Debug)]
-pub enum MulExp {
-    UnaryExp(UnaryExp),
-    BinaryMulExp(Box<MulExp>, UnaryExp), 
-    BinaryDivExp(Box<MulExp>, UnaryExp), 
-    BinaryModExp(Box<MulExp>, UnaryExp), 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Lt, //小于
+    Gt, //大于
+    Le, //小于等于
+    Ge, //大于等于
+    Eq,
+    NotEq,
+    And, //逻辑与 &&, 短路求值
+    Or,  //逻辑或 ||, 短路求值
 }
-#[derive(Debug)]
-pub enum AddExp {
-    MulExp(MulExp),
-    BinaryAddExp(Box<AddExp>, MulExp), 
-    BinarySubExp(Box<AddExp>, MulExp), 
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Lt => "<",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Eq => "==",
+            BinaryOp::NotEq => "!=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+        };
+        write!(f, "{}", op)
+    }
 }
-#[derive(Debug)]
-pub enum LOrExp {
-    LAndExp(LAndExp),
-    BinaryLOrExp(Box<LOrExp>,LAndExp),
-}
-#[derive(Debug)]
-pub enum  LAndExp {
-    EqExp(EqExp),
-    BinaryLAndExp(Box<LAndExp>,EqExp),
-}
-#[derive(Debug)]
-pub enum EqExp {
-    RelExp(RelExp),
-    BinaryEqExp(Box<EqExp>,RelExp),
-    BinaryNotEqExp(Box<EqExp>,RelExp),
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Plus,
+    Minus,
+    Not,
 }
-#[derive(Debug)]
-pub enum RelExp {
-    AddExp(AddExp),
-    BinaryLtRelExp(Box<RelExp>,AddExp),//小于
-    BinaryGtRelExp(Box<RelExp>,AddExp),//大于
-    BinaryLeRelExp(Box<RelExp>,AddExp),//小于等于
-    BinaryGeRelExp(Box<RelExp>,AddExp),//大于等于
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            UnaryOp::Plus => "+",
+            UnaryOp::Minus => "-",
+            UnaryOp::Not => "!",
+        };
+        write!(f, "{}", op)
+    }
 }
@@ -1,22 +1,43 @@
 //! Definition Statement of the Abstract Syntax Tree (AST). 
 
 use crate::ast::exp::*;
+use crate::error::{CompileError, Span};
 
 #[derive(Debug)]
 pub struct CompUnit {
-    pub func_def: FuncDef,
+    pub items: Vec<GlobalItem>,
+}
+
+/// 编译单元中的顶层定义
+#[derive(Debug)]
+pub enum GlobalItem {
+    FuncDef(FuncDef),
+    Decl(Decl),
+    /// 语法错误, 解析器从这里恢复后继续
+    Error(Span),
 }
 
 #[derive(Debug)]
 pub struct FuncDef {
     pub return_type: BType,
     pub func_id: String,
+    pub span: Span, //函数名的位置
+    pub params: Vec<FuncFParam>,
     pub block: Block,
 }
 
+/// 函数形参
+#[derive(Debug)]
+pub enum FuncFParam {
+    FuncFParam(BType, IDENT, Span),
+    /// 数组参数 int a[][N]..., 记录第一维之后各维的长度
+    ArrayParam(BType, IDENT, Vec<ConstExp>, Span),
+}
+
 #[derive(Debug)]
 pub struct BType {
     pub type_name: String,
+    pub span: Span,
 }
 #[derive(Debug)]
 pub enum Decl{
@@ -25,46 +46,71 @@ pub enum Decl{
 }
 #[derive(Debug)]
 pub enum ConstDecl{
-    ConstDecl(BType,Vec<ConstDef>),
+    ConstDecl(BType,Vec<ConstDef>,Span),
 }
 #[derive(Debug)]
 pub enum ConstDef {
-    ConstDef(IDENT, ConstInitVal),
+    /// 标识符, 各维长度 (标量时为空), 初始值
+    ConstDef(IDENT, Vec<ConstExp>, ConstInitVal, Span),
 }
 #[derive(Debug)]
 pub struct IDENT {
     pub content: String,
+    pub span: Span,
+    pub symbol: Option<SymbolId>, //语义分析时解析到的定义, 函数名为 None
 }
+/// 语义分析时为每个变量、常量与参数的定义分配的编号, 同名的不同定义编号不同
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymbolId(pub usize);
 
 #[derive(Debug)]
 pub enum ConstInitVal {
     ConstExp(ConstExp),
+    InitList(Vec<ConstInitVal>, Span),
 }
 #[derive(Debug)]
 pub enum ConstExp {
-    Exp(Exp),
+    Exp(Expr),
+}
+impl ConstExp {
+    pub fn span(&self) -> Span {
+        match self {
+            ConstExp::Exp(exp) => exp.span(),
+        }
+    }
 }
 #[derive(Debug)]
 pub enum LVal {
     IDENT(IDENT),
+    /// 数组元素 a[i][j]
+    ArrayElem(IDENT, Vec<Expr>, Span),
+}
+impl LVal {
+    pub fn span(&self) -> Span {
+        match self {
+            LVal::IDENT(ident) => ident.span,
+            LVal::ArrayElem(_, _, span) => *span,
+        }
+    }
 }
 #[derive(Debug)]
 pub enum VarDecl{
-    VarDecl(BType,Vec<VarDef>),
+    VarDecl(BType,Vec<VarDef>,Span),
 }
 #[derive(Debug)]
 pub enum VarDef {
-    VarDef(IDENT, InitVal),
-    IDENT(IDENT),
+    VarDef(IDENT, Vec<ConstExp>, InitVal, Span),
+    IDENT(IDENT, Vec<ConstExp>, Span),
 }
 #[derive(Debug)]
 pub enum InitVal {
-    Exp(Exp),
+    Exp(Expr),
+    InitList(Vec<InitVal>, Span),
 }
 #[derive(Debug)]
 /// 代码块
 pub enum Block {
-    Block(Vec<BlockItem>),
+    Block(Vec<BlockItem>, Span),
 }
 #[derive(Debug)]
 pub enum BlockItem{
@@ -74,11 +120,179 @@ pub enum BlockItem{
 /// Stmt内容
 #[derive(Debug)]
 pub enum Stmt {
-    ReturnStmt(Exp),
-    AssignStmt(LVal,Exp),
+    ReturnStmt(Option<Expr>,Span),
+    AssignStmt(LVal,Expr,Span),
+    ExpStmt(Option<Expr>,Span),
+    BlockStmt(Block),
+    /// if (cond) then_stmt [else else_stmt]
+    IfStmt(Expr,Box<Stmt>,Option<Box<Stmt>>,Span),
+    /// while (cond) body
+    WhileStmt(Expr,Box<Stmt>,Span),
+    BreakStmt(Span),
+    ContinueStmt(Span),
+    /// 语法错误, 解析器跳过了直到 ';' 或 '}' 的记号
+    Error(Span),
 }
 
-#[derive(Debug)]
-pub enum Number {
-    IntConst(i32),
-}
\ No newline at end of file
+/// 把整数字面量按无符号 32 位整数解析, 再按 C 的回绕规则转换成 i32.
+/// 例如 2147483648 (用于写出 -2147483648) 与 0xFFFFFFFF 分别得到 i32::MIN 与 -1.
+pub fn parse_int_const(digits: &str, radix: u32, span: Span) -> Result<i32, CompileError> {
+    match u32::from_str_radix(digits, radix) {
+        Ok(value) => Ok(value as i32),
+        Err(_) => Err(CompileError::Lexing(
+            "integer literal is too large to be represented in 32 bits".to_string(),
+            span,
+        )),
+    }
+}
+
+/// 可以嵌套的初始化列表
+pub trait InitList: Sized {
+    fn sub_list(&self) -> Option<&[Self]>;
+}
+impl InitList for ConstInitVal {
+    fn sub_list(&self) -> Option<&[Self]> {
+        match self {
+            ConstInitVal::ConstExp(_) => None,
+            ConstInitVal::InitList(list, _) => Some(list),
+        }
+    }
+}
+impl InitList for InitVal {
+    fn sub_list(&self) -> Option<&[Self]> {
+        match self {
+            InitVal::Exp(_) => None,
+            InitVal::InitList(list, _) => Some(list),
+        }
+    }
+}
+
+/// 按 SysY 的规则把初始化列表展开成按行优先排列的元素, 缺失的元素用 None 补齐.
+///
+/// 遇到子列表时, 它对应的是从当前位置开始、能被当前位置整除的最大的子数组.
+/// 例如 int a[2][3] = {{1}, {2, 3}} 展开为 1, 0, 0, 2, 3, 0.
+pub fn flatten_init_list<'a, T: InitList>(
+    items: &'a [T],
+    dims: &[usize],
+    flattened: &mut Vec<Option<&'a T>>,
+) -> Result<(), String> {
+    let total: usize = dims.iter().product();
+    let start = flattened.len();
+    for item in items {
+        let pos = flattened.len() - start;
+        if pos >= total {
+            return Err("Excess elements in array initializer".to_string());
+        }
+        match item.sub_list() {
+            None => flattened.push(Some(item)),
+            Some(list) => {
+                //找到从当前位置开始对齐的子数组
+                let mut sub = 1;
+                while sub < dims.len() && !pos.is_multiple_of(dims[sub..].iter().product::<usize>()) {
+                    sub += 1;
+                }
+                if sub == dims.len() {
+                    return Err("Initializer list is not aligned to a sub-array".to_string());
+                }
+                flatten_init_list(list, &dims[sub..], flattened)?;
+            }
+        }
+    }
+    flattened.resize(start + total, None);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(digits: &str, radix: u32) -> Result<i32, CompileError> {
+        parse_int_const(digits, radix, Span::new(0, digits.len()))
+    }
+
+    #[test]
+    fn int_const_radix() {
+        assert_eq!(parse("123", 10).unwrap(), 123);
+        assert_eq!(parse("017", 8).unwrap(), 15);
+        assert_eq!(parse("0", 8).unwrap(), 0);
+        assert_eq!(parse("7fFF", 16).unwrap(), 0x7fff);
+    }
+
+    #[test]
+    fn int_const_wraps_like_c() {
+        //-2147483648 是对 2147483648 取负, 字面量本身按 32 位补码回绕
+        assert_eq!(parse("2147483648", 10).unwrap(), i32::MIN);
+        assert_eq!(parse("4294967295", 10).unwrap(), -1);
+        assert_eq!(parse("80000000", 16).unwrap(), i32::MIN);
+        assert_eq!(parse("037777777777", 8).unwrap(), -1);
+    }
+
+    #[test]
+    fn int_const_overflow() {
+        for (digits, radix) in [("4294967296", 10), ("100000000", 16), ("040000000000", 8)] {
+            let err = parse(digits, radix).unwrap_err();
+            assert!(matches!(err, CompileError::Lexing(..)), "{:?}", err);
+            assert_eq!(err.span(), Span::new(0, digits.len()));
+        }
+    }
+
+    fn lit(value: i32) -> InitVal {
+        InitVal::Exp(Expr::Lit(value, Span::default()))
+    }
+    fn list(items: Vec<InitVal>) -> InitVal {
+        InitVal::InitList(items, Span::default())
+    }
+    //展开后的元素, 缺失的元素记为 None
+    fn flatten(items: &[InitVal], dims: &[usize]) -> Result<Vec<Option<i32>>, String> {
+        let mut flattened = Vec::new();
+        flatten_init_list(items, dims, &mut flattened)?;
+        Ok(flattened
+            .into_iter()
+            .map(|item| item.map(|init_val| match init_val {
+                InitVal::Exp(Expr::Lit(value, _)) => *value,
+                _ => unreachable!(),
+            }))
+            .collect())
+    }
+
+    #[test]
+    fn init_list_is_padded() {
+        assert_eq!(flatten(&[lit(1), lit(2)], &[4]).unwrap(), [Some(1), Some(2), None, None]);
+        assert_eq!(flatten(&[], &[2, 2]).unwrap(), [None; 4]);
+    }
+
+    #[test]
+    fn sub_lists_fill_aligned_sub_arrays() {
+        //int a[2][3] = {{1}, {2, 3}}
+        let items = [list(vec![lit(1)]), list(vec![lit(2), lit(3)])];
+        assert_eq!(flatten(&items, &[2, 3]).unwrap(), [Some(1), None, None, Some(2), Some(3), None]);
+        //int a[2][2][2] = {1, 2, {3}, {4, 5, 6}}: {3} 对齐到 a[0][1], {4, 5, 6} 对齐到 a[1]
+        let items = [lit(1), lit(2), list(vec![lit(3)]), list(vec![lit(4), lit(5), lit(6)])];
+        assert_eq!(
+            flatten(&items, &[2, 2, 2]).unwrap(),
+            [Some(1), Some(2), Some(3), None, Some(4), Some(5), Some(6), None]
+        );
+    }
+
+    #[test]
+    fn elements_without_braces_fill_in_order() {
+        let items: Vec<InitVal> = (1..=5).map(lit).collect();
+        assert_eq!(flatten(&items, &[2, 3]).unwrap(), [Some(1), Some(2), Some(3), Some(4), Some(5), None]);
+    }
+
+    #[test]
+    fn excess_elements_are_rejected() {
+        assert_eq!(flatten(&[lit(1), lit(2), lit(3)], &[2]).unwrap_err(), "Excess elements in array initializer");
+        let items = [list(vec![lit(1)]), list(vec![lit(2)]), list(vec![lit(3)])];
+        assert_eq!(flatten(&items, &[2, 1]).unwrap_err(), "Excess elements in array initializer");
+    }
+
+    #[test]
+    fn misaligned_sub_list_is_rejected() {
+        //int a[2][2] = {1, {2}}: {2} 不能从 a[0][1] 开始
+        assert_eq!(
+            flatten(&[lit(1), list(vec![lit(2)])], &[2, 2]).unwrap_err(),
+            "Initializer list is not aligned to a sub-array"
+        );
+    }
+}
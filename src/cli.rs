@@ -0,0 +1,297 @@
+//! 命令行参数的解析.
+//! 用法: compiler-pku <mode> <input> -o <output> [options], 选项的顺序任意.
+
+use compiler_pku::error::{WarningKind, WarningOptions};
+use compiler_pku::optimizer::OptLevel;
+use compiler_pku::{Emit, Options};
+
+pub const NAME: &str = env!("CARGO_PKG_NAME");
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 一次编译所需的参数
+#[derive(Debug)]
+pub struct Args {
+    pub options: Options,
+    pub input: String, //源文件, "-" 表示标准输入
+    pub output: String,
+    pub warning_options: WarningOptions,
+    pub dumps: Dumps,
+}
+
+/// 调试用的中间结果输出到哪里
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DumpTarget {
+    Stderr,       //--dump-xxx
+    File(String), //--dump-xxx=<file>
+}
+
+/// --dump-ast, --dump-symbols, --dump-ir 与 --dump-asm 选项
+#[derive(Debug, Default)]
+pub struct Dumps {
+    pub ast: Option<DumpTarget>,
+    pub symbols: Option<DumpTarget>,
+    pub ir: Option<DumpTarget>,
+    pub asm: Option<DumpTarget>,
+}
+
+/// 编译模式, 决定输出的内容与默认的优化级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Koopa, //-koopa: 输出 Koopa IR
+    Riscv, //-riscv: 输出 RISC-V 汇编
+    Perf,  //-perf: 输出用于性能测试的 RISC-V 汇编, 默认开启优化
+}
+impl Mode {
+    fn from_flag(flag: &str) -> Option<Mode> {
+        match flag {
+            "-koopa" => Some(Mode::Koopa),
+            "-riscv" => Some(Mode::Riscv),
+            "-perf" => Some(Mode::Perf),
+            _ => None,
+        }
+    }
+    fn flag(&self) -> &'static str {
+        match self {
+            Mode::Koopa => "-koopa",
+            Mode::Riscv => "-riscv",
+            Mode::Perf => "-perf",
+        }
+    }
+}
+
+/// 命令行要求执行的动作
+#[derive(Debug)]
+pub enum Command {
+    Compile(Args),
+    Help,
+    Version,
+}
+
+/// 解析命令行参数 (不含程序名), 出错时返回错误信息
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut mode: Option<Mode> = None;
+    let mut input: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut opt_level: Option<OptLevel> = None;
+    let mut warning_options = WarningOptions::default();
+    let mut dumps = Dumps::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if let Some(new_mode) = Mode::from_flag(&arg) {
+            match mode {
+                Some(old_mode) if old_mode != new_mode => {
+                    return Err(format!("conflicting modes '{}' and '{}'", old_mode.flag(), new_mode.flag()));
+                }
+                _ => mode = Some(new_mode),
+            }
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-o" => {
+                let file = match args.next() {
+                    Some(file) if !file.is_empty() && (file == "-" || !file.starts_with('-')) => file,
+                    _ => return Err("missing file name after '-o'".to_string()),
+                };
+                if let Some(old_file) = &output {
+                    return Err(format!("multiple output files: '{}' and '{}'", old_file, file));
+                }
+                output = Some(file);
+            }
+            //-O2 目前没有额外的优化, 与 -O1 相同
+            "-O" | "-O1" | "-O2" => opt_level = Some(OptLevel::O1),
+            "-O0" => opt_level = Some(OptLevel::O0),
+            _ if arg.starts_with("-O") => {
+                return Err(format!("unknown optimization level '{}', expected -O0, -O1 or -O2", arg));
+            }
+            _ if arg.starts_with("-W") || arg == "-w" => warning_options.apply(&arg)?,
+            _ if arg.starts_with("--dump-") => {
+                let (name, target) = match arg.split_once('=') {
+                    Some((_, "")) => return Err(format!("missing file name in '{}'", arg)),
+                    Some((name, file)) => (name, DumpTarget::File(file.to_string())),
+                    None => (arg.as_str(), DumpTarget::Stderr),
+                };
+                let dump = match name {
+                    "--dump-ast" => &mut dumps.ast,
+                    "--dump-symbols" => &mut dumps.symbols,
+                    "--dump-ir" => &mut dumps.ir,
+                    "--dump-asm" => &mut dumps.asm,
+                    _ => return Err(format!("unknown option '{}'", arg)),
+                };
+                *dump = Some(target);
+            }
+            _ if arg == "-" || !arg.starts_with('-') => {
+                if let Some(old_input) = &input {
+                    return Err(format!("multiple input files: '{}' and '{}'", old_input, arg));
+                }
+                input = Some(arg);
+            }
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+    let mode = mode.ok_or("no mode given, expected -koopa, -riscv or -perf")?;
+    let input = input.ok_or("no input file")?;
+    let output = output.ok_or("no output file, use -o <file>")?;
+    if output == "-" {
+        return Err("writing to standard output is not supported, use -o <file>".to_string());
+    }
+    if output == input {
+        return Err(format!("output file '{}' is the same as the input file", output));
+    }
+    let options = match mode {
+        Mode::Koopa => Options { emit: Emit::Koopa, opt_level: opt_level.unwrap_or(OptLevel::O0) },
+        Mode::Riscv => Options { emit: Emit::Riscv, opt_level: opt_level.unwrap_or(OptLevel::O0) },
+        Mode::Perf => Options { emit: Emit::Riscv, opt_level: opt_level.unwrap_or(OptLevel::O1) },
+    };
+    Ok(Command::Compile(Args { options, input, output, warning_options, dumps }))
+}
+
+/// --help 输出的帮助信息
+pub fn usage() -> String {
+    let warning_names: Vec<&str> = WarningKind::ALL.iter().map(|kind| kind.name()).collect();
+    format!(
+        "\
+Usage: {NAME} <MODE> <INPUT> -o <OUTPUT> [OPTIONS]
+
+Compile a SysY source file to Koopa IR or RISC-V assembly.
+
+Modes:
+  -koopa           Emit Koopa IR
+  -riscv           Emit RISC-V assembly
+  -perf            Emit RISC-V assembly for performance testing (implies -O1)
+
+Arguments:
+  <INPUT>          SysY source file, or '-' to read from standard input
+
+Options:
+  -o <OUTPUT>      Write the output to <OUTPUT>
+  -O0, -O1         Optimization level (default: -O0)
+  -O, -O2          Same as -O1
+  -w               Disable all warnings
+  -Wall            Enable all warnings (the default)
+  -W<name>         Enable a warning
+  -Wno-<name>      Disable a warning
+  -Werror          Treat warnings as errors
+  --dump-ast[=<FILE>]
+                   Print the AST to stderr, or write it to <FILE>
+  --dump-symbols[=<FILE>]
+                   Print the functions and the symbols of each scope
+  --dump-ir[=<FILE>]
+                   Print the Koopa IR (after optimization)
+  --dump-asm[=<FILE>]
+                   Print the RISC-V assembly, also in -koopa mode
+  -h, --help       Print this help
+  -V, --version    Print the version
+
+Warnings: {}",
+        warning_names.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+    fn parse_compile(args: &[&str]) -> Args {
+        match parse(args) {
+            Ok(Command::Compile(args)) => args,
+            other => panic!("expected a compile command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn modes_select_output_and_default_opt_level() {
+        let args = parse_compile(&["-koopa", "a.c", "-o", "a.koopa"]);
+        assert_eq!(args.options, Options { emit: Emit::Koopa, opt_level: OptLevel::O0 });
+        assert_eq!((args.input.as_str(), args.output.as_str()), ("a.c", "a.koopa"));
+        let args = parse_compile(&["-riscv", "a.c", "-o", "a.s"]);
+        assert_eq!(args.options, Options { emit: Emit::Riscv, opt_level: OptLevel::O0 });
+        let args = parse_compile(&["-o", "a.s", "a.c", "-perf"]);
+        assert_eq!(args.options, Options { emit: Emit::Riscv, opt_level: OptLevel::O1 });
+        //重复同一个模式是允许的
+        assert!(parse(&["-riscv", "-riscv", "a.c", "-o", "a.s"]).is_ok());
+    }
+
+    #[test]
+    fn mode_errors() {
+        assert_eq!(
+            parse(&["a.c", "-o", "a.s"]).unwrap_err(),
+            "no mode given, expected -koopa, -riscv or -perf"
+        );
+        assert_eq!(
+            parse(&["-koopa", "-perf", "a.c", "-o", "a.s"]).unwrap_err(),
+            "conflicting modes '-koopa' and '-perf'"
+        );
+    }
+
+    #[test]
+    fn output_is_validated() {
+        assert_eq!(parse(&["-riscv", "a.c"]).unwrap_err(), "no output file, use -o <file>");
+        assert_eq!(parse(&["-riscv", "a.c", "-o"]).unwrap_err(), "missing file name after '-o'");
+        assert_eq!(parse(&["-riscv", "a.c", "-o", "-O1"]).unwrap_err(), "missing file name after '-o'");
+        assert_eq!(
+            parse(&["-riscv", "a.c", "-o", "a.s", "-o", "b.s"]).unwrap_err(),
+            "multiple output files: 'a.s' and 'b.s'"
+        );
+        assert_eq!(
+            parse(&["-riscv", "a.c", "-o", "-"]).unwrap_err(),
+            "writing to standard output is not supported, use -o <file>"
+        );
+        assert_eq!(
+            parse(&["-riscv", "a.c", "-o", "a.c"]).unwrap_err(),
+            "output file 'a.c' is the same as the input file"
+        );
+        assert_eq!(parse(&["-riscv", "-o", "a.s"]).unwrap_err(), "no input file");
+        //"-" 表示从标准输入读取
+        assert_eq!(parse_compile(&["-riscv", "-", "-o", "a.s"]).input, "-");
+    }
+
+    #[test]
+    fn opt_levels() {
+        let opt_level = |flag: &str| parse_compile(&["-riscv", "a.c", "-o", "a.s", flag]).options.opt_level;
+        assert_eq!(opt_level("-O0"), OptLevel::O0);
+        assert_eq!(opt_level("-O"), OptLevel::O1);
+        assert_eq!(opt_level("-O1"), OptLevel::O1);
+        assert_eq!(opt_level("-O2"), OptLevel::O1);
+        //显式的优化级别优先于 -perf 的默认值
+        assert_eq!(parse_compile(&["-perf", "a.c", "-o", "a.s", "-O0"]).options.opt_level, OptLevel::O0);
+        assert_eq!(
+            parse(&["-riscv", "a.c", "-o", "a.s", "-O3"]).unwrap_err(),
+            "unknown optimization level '-O3', expected -O0, -O1 or -O2"
+        );
+    }
+
+    #[test]
+    fn warning_flags() {
+        let args = parse_compile(&["-riscv", "a.c", "-o", "a.s", "-Wno-unused-value", "-Werror"]);
+        assert!(args.warning_options.werror);
+        assert!(!args.warning_options.is_enabled(WarningKind::UnusedValue));
+        assert!(args.warning_options.is_enabled(WarningKind::ReturnType));
+        let args = parse_compile(&["-riscv", "a.c", "-o", "a.s", "-w"]);
+        assert!(WarningKind::ALL.iter().all(|&kind| !args.warning_options.is_enabled(kind)));
+        assert_eq!(
+            parse(&["-riscv", "a.c", "-o", "a.s", "-Wfoo"]).unwrap_err(),
+            "unknown warning option '-Wfoo'"
+        );
+    }
+
+    #[test]
+    fn dump_flags() {
+        let args = parse_compile(&["-koopa", "a.c", "-o", "a.koopa", "--dump-ast", "--dump-ir=ir.txt"]);
+        assert_eq!(args.dumps.ast, Some(DumpTarget::Stderr));
+        assert_eq!(args.dumps.ir, Some(DumpTarget::File("ir.txt".to_string())));
+        assert_eq!(args.dumps.symbols, None);
+        assert_eq!(parse(&["--dump-asm="]).unwrap_err(), "missing file name in '--dump-asm='");
+        assert_eq!(parse(&["--dump-cfg"]).unwrap_err(), "unknown option '--dump-cfg'");
+    }
+
+    #[test]
+    fn help_and_version_win() {
+        assert!(matches!(parse(&["-riscv", "--help", "-O9"]), Ok(Command::Help)));
+        assert!(matches!(parse(&["-V"]), Ok(Command::Version)));
+    }
+}
@@ -0,0 +1,365 @@
+//! 编译过程中各个阶段产生的错误, 以及把错误定位到源代码并打印出来的方法.
+
+use std::fmt;
+
+use lalrpop_util::lexer::Token;
+use lalrpop_util::ParseError;
+
+/// 源代码中的一段区间, 以字节为单位, 左闭右开
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+}
+
+/// 编译错误, 每种错误都带有出错位置在源代码中的区间
+#[derive(Debug)]
+pub enum CompileError {
+    Lexing(String, Span),   //词法错误: 无法识别的字符
+    Parsing(String, Span),  //语法错误
+    Semantic(String, Span), //语义错误: 未定义的符号、类型不匹配等
+    Codegen(String, Span),  //生成汇编时的错误
+    Io(std::io::Error),     //写出结果时的 I/O 错误, 与源代码中的位置无关
+}
+impl CompileError {
+    pub fn message(&self) -> String {
+        match self {
+            CompileError::Lexing(message, _)
+            | CompileError::Parsing(message, _)
+            | CompileError::Semantic(message, _)
+            | CompileError::Codegen(message, _) => message.clone(),
+            CompileError::Io(err) => err.to_string(),
+        }
+    }
+    /// 出错的位置, I/O 错误没有位置, 为空区间
+    pub fn span(&self) -> Span {
+        match self {
+            CompileError::Lexing(_, span)
+            | CompileError::Parsing(_, span)
+            | CompileError::Semantic(_, span)
+            | CompileError::Codegen(_, span) => *span,
+            CompileError::Io(_) => Span::default(),
+        }
+    }
+    fn kind(&self) -> &'static str {
+        match self {
+            CompileError::Lexing(_, _) => "lexical error",
+            CompileError::Parsing(_, _) => "syntax error",
+            CompileError::Semantic(_, _) => "error",
+            CompileError::Codegen(_, _) => "codegen error",
+            CompileError::Io(_) => "I/O error",
+        }
+    }
+
+    /// 生成形如 `file:line:col: error: message` 的诊断信息, 并附上出错的那一行和指向出错位置的 ^.
+    /// I/O 错误只有种类与内容
+    pub fn report(&self, file_name: &str, source: &str) -> String {
+        match self {
+            CompileError::Io(err) => format!("{}: {}", self.kind(), err),
+            _ => render(self.kind(), &self.message(), self.span(), file_name, source),
+        }
+    }
+}
+impl From<std::io::Error> for CompileError {
+    fn from(err: std::io::Error) -> Self {
+        CompileError::Io(err)
+    }
+}
+
+/// 编译警告: 程序是合法的, 但很可能不是作者想要的
+#[derive(Debug)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub message: String,
+    pub span: Span,
+}
+impl Warning {
+    pub fn new(kind: WarningKind, message: String, span: Span) -> Self {
+        Warning { kind, message, span }
+    }
+    /// 与错误的格式相同, 在信息后面注明控制这种警告的选项; as_error 为真时 (-Werror) 作为错误报告
+    pub fn report(&self, file_name: &str, source: &str, as_error: bool) -> String {
+        let (kind, flag) = if as_error {
+            ("error", format!("-Werror,-W{}", self.kind.name()))
+        } else {
+            ("warning", format!("-W{}", self.kind.name()))
+        };
+        render(kind, &format!("{} [{}]", self.message, flag), self.span, file_name, source)
+    }
+}
+
+/// 警告的种类, 每种警告都可以用 -W<name> 与 -Wno-<name> 单独开关
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind {
+    UnusedVariable,      //定义了但从来没有读过的局部变量
+    UnusedValue,         //被丢弃的表达式的值
+    SelfAssign,          //把变量赋值给它自己
+    TautologicalCompare, //常量折叠后结果恒定的比较
+    ReturnType,          //非 void 函数可能执行到末尾而没有 return
+}
+impl WarningKind {
+    pub const ALL: [WarningKind; 5] = [
+        WarningKind::UnusedVariable,
+        WarningKind::UnusedValue,
+        WarningKind::SelfAssign,
+        WarningKind::TautologicalCompare,
+        WarningKind::ReturnType,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            WarningKind::UnusedVariable => "unused-variable",
+            WarningKind::UnusedValue => "unused-value",
+            WarningKind::SelfAssign => "self-assign",
+            WarningKind::TautologicalCompare => "tautological-compare",
+            WarningKind::ReturnType => "return-type",
+        }
+    }
+}
+
+/// 命令行中的 -W 选项, 默认开启所有警告
+#[derive(Debug, Clone, Default)]
+pub struct WarningOptions {
+    disabled: Vec<WarningKind>,
+    pub werror: bool, //-Werror: 把警告当作错误
+}
+impl WarningOptions {
+    /// 按顺序应用一个选项: -w, -Wall, -Werror, -Wno-error, -W<name>, -Wno-<name>
+    pub fn apply(&mut self, flag: &str) -> Result<(), String> {
+        match flag {
+            "-w" => self.disabled = WarningKind::ALL.to_vec(),
+            "-Wall" => self.disabled.clear(),
+            "-Werror" => self.werror = true,
+            "-Wno-error" => self.werror = false,
+            _ => {
+                let (name, enable) = match flag.strip_prefix("-Wno-") {
+                    Some(name) => (name, false),
+                    None => (flag.strip_prefix("-W").unwrap_or(flag), true),
+                };
+                let kind = WarningKind::ALL
+                    .into_iter()
+                    .find(|kind| kind.name() == name)
+                    .ok_or(format!("unknown warning option '{}'", flag))?;
+                self.disabled.retain(|disabled| *disabled != kind);
+                if !enable {
+                    self.disabled.push(kind);
+                }
+            }
+        }
+        Ok(())
+    }
+    pub fn is_enabled(&self, kind: WarningKind) -> bool {
+        !self.disabled.contains(&kind)
+    }
+}
+
+/// 诊断信息的格式: 位置、种类与内容, 然后是源代码中的那一行, 用 ^ 标出区间
+fn render(kind: &str, message: &str, span: Span, file_name: &str, source: &str) -> String {
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |pos| pos + 1);
+    let line_end = source[start..].find('\n').map_or(source.len(), |pos| start + pos);
+    let line = source[..start].matches('\n').count() + 1;
+    let col = source[line_start..start].chars().count() + 1;
+    let source_line = source[line_start..line_end].trim_end_matches('\r');
+    //^ 的数量与区间在这一行内的长度一致, 至少一个
+    let mut end = span.end.clamp(start, line_end);
+    while !source.is_char_boundary(end) {
+        end += 1;
+    }
+    let width = source[start..end].chars().count().max(1);
+    //保留制表符, 使 ^ 与源代码对齐
+    let padding: String = source[line_start..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!(
+        "{}:{}:{}: {}: {}\n{}\n{}{}",
+        file_name,
+        line,
+        col,
+        kind,
+        message,
+        source_line,
+        padding,
+        "^".repeat(width)
+    )
+}
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind(), self.message())
+    }
+}
+impl std::error::Error for CompileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompileError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl CompileError {
+    /// 把 lalrpop 的 ParseError 转换成编译错误, 期望的记号用 SysY 中的说法列出.
+    /// 需要源代码来找到出错位置前面的那个记号.
+    pub fn from_parse_error(error: ParseError<usize, Token<'_>, CompileError>, source: &str) -> Self {
+        match error {
+            ParseError::InvalidToken { location } => {
+                let found = source[location..].chars().next().unwrap_or(' ');
+                CompileError::Lexing(format!("invalid character '{}'", found), Span::new(location, location + 1))
+            }
+            ParseError::UnrecognizedEof { location, expected } => CompileError::Parsing(
+                format!("expected {}, found end of file", describe_expected(&expected)),
+                Span::new(location, location),
+            ),
+            ParseError::UnrecognizedToken { token: (start, token, end), expected } => {
+                let message = format!("expected {}, found '{}'", describe_expected(&expected), token);
+                //缺少 ';' 时, 出错的记号往往已经在下一行了, 把位置指向上一个记号的末尾
+                let prev_end = prev_token_end(source, start);
+                if expected.iter().any(|terminal| terminal == "\";\"") && source[prev_end..start].contains('\n') {
+                    return CompileError::Parsing(message, Span::new(prev_end, prev_end));
+                }
+                CompileError::Parsing(message, Span::new(start, end))
+            }
+            ParseError::ExtraToken { token: (start, token, end) } => {
+                CompileError::Parsing(format!("unexpected '{}' after the end of the program", token), Span::new(start, end))
+            }
+            ParseError::User { error } => error,
+        }
+    }
+}
+
+//二元运算符, 期望的记号中有多个运算符时合并成 "an operator"
+const BINARY_OPERATORS: [&str; 13] = ["+", "-", "*", "/", "%", "<", ">", "<=", ">=", "==", "!=", "&&", "||"];
+
+/// 把 lalrpop 的终结符名字 (如 "\";\"" 或 r#"[1-9][0-9]*"#) 转换成 SysY 中的说法
+fn describe_terminal(terminal: &str) -> String {
+    if terminal.starts_with("r#") {
+        //正则表达式只用于标识符和整数常量
+        if terminal.contains("_a-zA-Z") {
+            "identifier".to_string()
+        } else {
+            "integer constant".to_string()
+        }
+    } else {
+        format!("'{}'", terminal.trim_matches('"'))
+    }
+}
+
+/// 列出期望的记号, 形如 "';', an operator or ')'"
+fn describe_expected(expected: &[String]) -> String {
+    let mut items: Vec<String> = Vec::new();
+    let operator_count = expected
+        .iter()
+        .filter(|terminal| BINARY_OPERATORS.contains(&terminal.trim_matches('"')))
+        .count();
+    for terminal in expected {
+        let item = if operator_count > 2 && BINARY_OPERATORS.contains(&terminal.trim_matches('"')) {
+            "an operator".to_string()
+        } else {
+            describe_terminal(terminal)
+        };
+        if !items.contains(&item) {
+            items.push(item);
+        }
+    }
+    //能开始一个表达式的记号很多, 合并成 "an expression"
+    let expression_start = ["identifier", "integer constant", "'('"];
+    if expression_start.iter().all(|item| items.iter().any(|x| x == item)) {
+        items.retain(|item| !expression_start.contains(&item.as_str()) && !["'+'", "'-'", "'!'"].contains(&item.as_str()));
+        items.insert(0, "an expression".to_string());
+    }
+    match items.len() {
+        0 => "nothing".to_string(),
+        1 => items.remove(0),
+        len => format!("{} or {}", items[..len - 1].join(", "), items[len - 1]),
+    }
+}
+
+/// 跳过空白和注释, 找到 pos 之前最后一个记号的结束位置
+fn prev_token_end(source: &str, pos: usize) -> usize {
+    let mut end = pos;
+    loop {
+        let trimmed = source[..end].trim_end();
+        end = trimmed.len();
+        if trimmed.ends_with("*/") {
+            match trimmed.rfind("/*") {
+                Some(comment_start) => end = comment_start,
+                None => return end,
+            }
+            continue;
+        }
+        //单行注释: 当前行中 "//" 之后的内容都是注释
+        let line_start = trimmed.rfind('\n').map_or(0, |i| i + 1);
+        match trimmed[line_start..].find("//") {
+            Some(comment_start) => end = line_start + comment_start,
+            None => return end,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //依次应用选项, 返回开启的警告
+    fn enabled_after(flags: &[&str]) -> Vec<WarningKind> {
+        let mut options = WarningOptions::default();
+        for flag in flags {
+            options.apply(flag).unwrap();
+        }
+        WarningKind::ALL.into_iter().filter(|&kind| options.is_enabled(kind)).collect()
+    }
+
+    #[test]
+    fn all_warnings_are_enabled_by_default() {
+        assert_eq!(enabled_after(&[]), WarningKind::ALL);
+        assert!(!WarningOptions::default().werror);
+    }
+
+    #[test]
+    fn later_flags_override_earlier_ones() {
+        assert_eq!(enabled_after(&["-w"]), []);
+        assert_eq!(enabled_after(&["-w", "-Wreturn-type"]), [WarningKind::ReturnType]);
+        assert_eq!(enabled_after(&["-w", "-Wall"]), WarningKind::ALL);
+        assert_eq!(
+            enabled_after(&["-Wno-unused-variable", "-Wno-self-assign", "-Wself-assign"]),
+            [
+                WarningKind::UnusedValue,
+                WarningKind::SelfAssign,
+                WarningKind::TautologicalCompare,
+                WarningKind::ReturnType,
+            ]
+        );
+    }
+
+    #[test]
+    fn werror_can_be_turned_off() {
+        let mut options = WarningOptions::default();
+        options.apply("-Werror").unwrap();
+        assert!(options.werror);
+        options.apply("-Wno-error").unwrap();
+        assert!(!options.werror);
+    }
+
+    #[test]
+    fn unknown_warning_is_rejected() {
+        let mut options = WarningOptions::default();
+        assert_eq!(options.apply("-Wshadow").unwrap_err(), "unknown warning option '-Wshadow'");
+        assert_eq!(options.apply("-Wno-shadow").unwrap_err(), "unknown warning option '-Wno-shadow'");
+    }
+
+    #[test]
+    fn warning_report_names_its_flag() {
+        let source = "int main() {\n  int x;\n}";
+        let warning = Warning::new(WarningKind::UnusedVariable, "unused".to_string(), Span::new(19, 20));
+        assert_eq!(
+            warning.report("t.c", source, false),
+            "t.c:2:7: warning: unused [-Wunused-variable]\n  int x;\n      ^"
+        );
+        assert!(warning.report("t.c", source, true).starts_with("t.c:2:7: error: unused [-Werror,-Wunused-variable]"));
+    }
+}
@@ -1,15 +1,16 @@
 //! Build a single component into Koopa IR.
 
-use crate::ast::{exp::*, statements::*};
-use koopa::ir::{builder_traits::*, BinaryOp, FunctionData, Program, Type};
-use super::MyIRGeneratorInfo;
+use crate::ast::{exp, exp::*, statements::*};
+use crate::error::Span;
+use koopa::ir::{builder_traits::*, BasicBlock, BinaryOp, FunctionData, Program, Type, TypeKind, Value, ValueKind};
+use super::{LoopInfo, MyIRGeneratorInfo};
 
 pub trait Buildable {
     fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String>;
+    );
 }
 
 impl Buildable for CompUnit {
@@ -17,9 +18,15 @@ impl Buildable for CompUnit {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        self.func_def.build(program, my_ir_generator_info)?;
-        Ok(())
+    ) {
+        for item in &self.items {
+            match item {
+                GlobalItem::FuncDef(func_def) => func_def.build(program, my_ir_generator_info),
+                GlobalItem::Decl(decl) => decl.build(program, my_ir_generator_info),
+                //有语法错误时不会生成 IR
+                GlobalItem::Error(_) => unreachable!("checked units have no syntax errors"),
+            }
+        }
     }
 }
 
@@ -28,19 +35,38 @@ impl Buildable for FuncDef {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
+    ) {
         let return_type = match self.return_type.type_name.as_str() {
-            "int" => Ok(Type::get_i32()),
-            _ => Err("Wrong return type"),
+            "int" => Type::get_i32(),
+            _ => Type::get_unit(),
         };
         //dbg!("Building function", &self);
+        //数组参数退化为指向第一维元素的指针
+        let mut param_types = Vec::new();
+        for param in &self.params {
+            param_types.push(match param {
+                FuncFParam::FuncFParam(_, ident, _) => (ident, Type::get_i32()),
+                FuncFParam::ArrayParam(_, ident, dims, _) => {
+                    let dims = const_dims(dims);
+                    (ident, Type::get_pointer(array_type(&dims)))
+                }
+            });
+        }
+        let params = param_types
+            .iter()
+            .map(|(ident, ty)| (Some(my_ir_generator_info.param_name(&ident.content)), ty.clone()))
+            .collect();
 
         //Create a new program for current program
         let func = program.new_func(FunctionData::with_param_names(
             "@".to_string() + self.func_id.as_str(),
-            vec![],
-            return_type.expect("Error creating new function"),
+            params,
+            return_type,
         ));
+        //先登记函数, 使函数体内可以递归调用
+        my_ir_generator_info
+            .function_table
+            .insert(self.func_id.clone(), func);
         //println!("{}", self.func_id.as_str());  //main
         let func_data = program.func_mut(func);
         let new_block = func_data
@@ -50,8 +76,38 @@ impl Buildable for FuncDef {
         func_data.layout_mut().bbs_mut().extend([new_block]);
         my_ir_generator_info.curr_block = Some(new_block);
         my_ir_generator_info.curr_func = Some(func);
-        self.block.build(program, my_ir_generator_info)?;
-        Ok(())
+        //为每个参数分配空间, 并把参数的值存进去
+        for (i, (ident, ty)) in param_types.into_iter().enumerate() {
+            let func_data = program.func_mut(func);
+            let param = func_data.params()[i];
+            let var_ptr = func_data.dfg_mut().new_value().alloc(ty.clone());
+            let name = my_ir_generator_info.unique_name(&ident.content);
+            let func_data = program.func_mut(func);
+            func_data.dfg_mut().set_value_name(var_ptr, Some(name));
+            let store_inst = func_data.dfg_mut().new_value().store(param, var_ptr);
+            push_inst(program, my_ir_generator_info, var_ptr);
+            push_inst(program, my_ir_generator_info, store_inst);
+            define_symbol(my_ir_generator_info, ident, var_ptr);
+        }
+        let Block::Block(block_items, _) = &self.block;
+        for block_item in block_items {
+            //return 之后的语句不可达, 直接丢弃
+            if is_curr_bb_terminated(program, my_ir_generator_info) {
+                break;
+            }
+            block_item.build(program, my_ir_generator_info);
+        }
+        //函数可能执行到末尾而没有 return, 补上 ret (int 函数返回 0)
+        if !is_curr_bb_terminated(program, my_ir_generator_info) {
+            let return_value = match self.return_type.type_name.as_str() {
+                "int" => Some(program.func_mut(func).dfg_mut().new_value().integer(0)),
+                _ => None,
+            };
+            let return_inst = program.func_mut(func).dfg_mut().new_value().ret(return_value);
+            push_inst(program, my_ir_generator_info, return_inst);
+        }
+        my_ir_generator_info.curr_func = None;
+        my_ir_generator_info.curr_block = None;
     }
 }
 
@@ -60,15 +116,18 @@ impl Buildable for Block {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
+    ) {
         match self {
-            Block::Block(block_items) =>{
+            Block::Block(block_items, _) =>{
                 for stmt in block_items{
-                    stmt.build(program, my_ir_generator_info)?
+                    //return/break/continue 之后的语句不可达, 直接丢弃
+                    if is_curr_bb_terminated(program, my_ir_generator_info) {
+                        break;
+                    }
+                    stmt.build(program, my_ir_generator_info)
                 }
             },
         }
-        Ok(())
     }
 }
 impl Buildable for BlockItem {
@@ -76,7 +135,7 @@ impl Buildable for BlockItem {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
+    ) {
         match self {
             BlockItem::Decl(decl) => decl.build(program, my_ir_generator_info),
             BlockItem::Stmt(stmt) => stmt.build(program, my_ir_generator_info),
@@ -88,7 +147,7 @@ impl Buildable for Decl {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
+    ) {
         match self{
             Decl::ConstDecl(const_decl) => const_decl.build(program, my_ir_generator_info),
             Decl::VarDecl(var_decl) => var_decl.build(program, my_ir_generator_info),
@@ -100,63 +159,62 @@ impl Buildable for ConstDef {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
+    ) {
         match self {
-            ConstDef::ConstDef(ident, const_initval) => {
-                const_initval.build(program, my_ir_generator_info)?;
-                let (ans,_) =my_ir_generator_info.tmp_constants.unwrap();
-                my_ir_generator_info.tmp_constants=None;
-                my_ir_generator_info.curr_symbols.insert(
-                    ident.content.clone(), 
-                    super::SymbolsEntry::Const(koopa::ir::TypeKind::Int32, ans)
-                );
-
-            },
-        }
-        Ok(())
-    }
-}
-impl Buildable for ConstInitVal {
-    fn build(
-        &self,
-        program: &mut Program,
-        my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self {
-            ConstInitVal::ConstExp(exp) => {
-                exp.build(program, my_ir_generator_info)
+            ConstDef::ConstDef(ident, dims, const_initval, _) => {
+                //标量常量的使用在语义分析时已经替换成了它的值
+                if dims.is_empty() {
+                    return;
+                }
+                //常量数组: 元素的值在语义分析时已经求出
+                let dims = const_dims(dims);
+                let list = match const_initval {
+                    ConstInitVal::InitList(list, _) => list,
+                    ConstInitVal::ConstExp(_) => unreachable!(),
+                };
+                let mut flattened = Vec::new();
+                flatten_init_list(list, &dims, &mut flattened).unwrap();
+                let values: Vec<i32> = flattened
+                    .into_iter()
+                    .map(|elem| match elem {
+                        Some(ConstInitVal::ConstExp(exp)) => const_value(exp),
+                        _ => 0,
+                    })
+                    .collect();
+                let array_ptr = if my_ir_generator_info.curr_func.is_none() {
+                    let init = build_aggregate(program, &dims, &values);
+                    let array_ptr = program.new_value().global_alloc(init);
+                    program.set_value_name(array_ptr, Some(format!("@{}", ident.content)));
+                    array_ptr
+                } else {
+                    let array_ptr = new_local_var(program, my_ir_generator_info, &ident.content, array_type(&dims));
+                    let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
+                    let init_values = values
+                        .iter()
+                        .map(|&value| curr_func_data.dfg_mut().new_value().integer(value))
+                        .collect::<Vec<_>>();
+                    store_init_values(program, my_ir_generator_info, array_ptr, &dims, &init_values);
+                    array_ptr
+                };
+                define_symbol(my_ir_generator_info, ident, array_ptr);
             },
         }
     }
 }
-impl Buildable for ConstExp {
-    fn build(
-        &self,
-        program: &mut Program,
-        my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self{
-            ConstExp::Exp(exp) => exp.build(program, my_ir_generator_info),
-        }
-    }
-}
 impl Buildable for ConstDecl {
     fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
+    ) {
         match self{
-            ConstDecl::ConstDecl(type_name, const_defs) => {
+            ConstDecl::ConstDecl(_type_name, const_defs, _) => {
                 //const int a=1,b=1;
                 for const_def in const_defs{
-                    //开始进行常量计算
-                    my_ir_generator_info.tmp_constants=Some((520,1314));
-                    const_def.build(program, my_ir_generator_info)?
+                    const_def.build(program, my_ir_generator_info)
                 }
             },
         }
-        Ok(())
     }
 }
 impl Buildable for VarDecl {
@@ -164,15 +222,14 @@ impl Buildable for VarDecl {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
+    ) {
         match self {
-            VarDecl::VarDecl(type_name, insides_def) => {
+            VarDecl::VarDecl(_type_name, insides_def, _) => {
                 for inside in insides_def{
                     inside.build(program, my_ir_generator_info);
                 }
             },
         }
-        Ok(())
     }
 }
 impl Buildable for VarDef {
@@ -180,97 +237,129 @@ impl Buildable for VarDef {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self{
-            VarDef::VarDef(ident, initval) => {
-                //定义变量的同时定义值
-                initval.build(program, my_ir_generator_info)?;
-                let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
-                let var_ptr =
-                    curr_func_data
-                        .dfg_mut()
-                        .new_value()
-                        .alloc(Type::get(koopa::ir::TypeKind::Int32));
-                curr_func_data.dfg_mut().set_value_name(var_ptr, Some(format!("@{}",ident.content)));
-                let store_inst=curr_func_data.dfg_mut().new_value().store(my_ir_generator_info.curr_value.unwrap(), var_ptr);
-                my_ir_generator_info.curr_symbols.insert(
-                    ident.content.clone(), 
-                    super::SymbolsEntry::Variable(koopa::ir::TypeKind::Int32, Some(var_ptr)));
-                curr_func_data
-                    .layout_mut()
-                    .bb_mut(my_ir_generator_info.curr_block.unwrap())
-                    .insts_mut()
-                    .extend([var_ptr,store_inst])
-            },
-            VarDef::IDENT(ident) => {
-                //定义变量，但不定义初始值
-                let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
-                let var_ptr =
-                    curr_func_data
-                        .dfg_mut()
-                        .new_value()
-                        .alloc(Type::get(koopa::ir::TypeKind::Int32));
-                curr_func_data.dfg_mut().set_value_name(var_ptr, Some(format!("@{}",ident.content)));
-                my_ir_generator_info.curr_symbols.insert(
-                    ident.content.clone(), 
-                    super::SymbolsEntry::Variable(koopa::ir::TypeKind::Int32, Some(var_ptr)));
-                curr_func_data
-                    .layout_mut()
-                    .bb_mut(my_ir_generator_info.curr_block.unwrap())
-                    .insts_mut()
-                    .extend([var_ptr])
-            },
+    ) {
+        let (ident, dims, initval) = match self {
+            VarDef::VarDef(ident, dims, initval, _) => (ident, dims, Some(initval)),
+            VarDef::IDENT(ident, dims, _) => (ident, dims, None),
+        };
+        let dims = const_dims(dims);
+        let var_type = array_type(&dims);
+        //把初始值展开成按行优先排列的元素列表, 缺失的元素为 None
+        let flattened = match initval {
+            None => None,
+            Some(InitVal::Exp(exp)) => Some(vec![Some(exp)]),
+            Some(InitVal::InitList(list, _)) => {
+                let mut flattened = Vec::new();
+                flatten_init_list(list, &dims, &mut flattened).unwrap();
+                Some(flattened
+                    .into_iter()
+                    .map(|elem| match elem {
+                        Some(InitVal::Exp(exp)) => Some(exp),
+                        _ => None,
+                    })
+                    .collect())
+            }
+        };
+        if my_ir_generator_info.curr_func.is_none() {
+            //全局变量: 初始值在语义分析时已经求出, 没有初始值时初始化为 0
+            let init = match flattened {
+                Some(flattened) => {
+                    let values: Vec<i32> = flattened
+                        .into_iter()
+                        .map(|exp| exp.map_or(0, lit_value))
+                        .collect();
+                    build_aggregate(program, &dims, &values)
+                }
+                None => program.new_value().zero_init(var_type.clone()),
+            };
+            let var_ptr = program.new_value().global_alloc(init);
+            program.set_value_name(var_ptr, Some(format!("@{}", ident.content)));
+            define_symbol(my_ir_generator_info, ident, var_ptr);
+            return;
         }
-        Ok(())
-    }
-}
-impl Buildable for InitVal {
-    fn build(
-        &self,
-        program: &mut Program,
-        my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self{
-            InitVal::Exp(exp) => exp.build(program, my_ir_generator_info),
+        //定义局部变量, 有初始值时依次计算并存入
+        let mut init_values = Vec::new();
+        if let Some(flattened) = &flattened {
+            for exp in flattened {
+                match exp {
+                    Some(exp) => {
+                        exp.build(program, my_ir_generator_info);
+                        init_values.push(my_ir_generator_info.curr_value.unwrap());
+                    }
+                    None => init_values.push(
+                        program
+                            .func_mut(my_ir_generator_info.curr_func.unwrap())
+                            .dfg_mut()
+                            .new_value()
+                            .integer(0),
+                    ),
+                }
+            }
         }
-    }
-}
-impl Buildable for Exp {
-    fn build(
-        &self,
-        program: &mut Program,
-        my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self {
-            Exp::LOrExp(lor_exp) => lor_exp.build(program, my_ir_generator_info),
+        let var_ptr = new_local_var(program, my_ir_generator_info, &ident.content, var_type.clone());
+        if flattened.is_some() {
+            store_init_values(program, my_ir_generator_info, var_ptr, &dims, &init_values);
         }
+        define_symbol(my_ir_generator_info, ident, var_ptr);
     }
 }
-impl Buildable for UnaryExp {
+impl Buildable for Expr {
     fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
+    ) {
         match self {
-            UnaryExp::PrimaryExp(primary_exp) => primary_exp.build(program, my_ir_generator_info),
-            UnaryExp::PlusUnaryExp(plus_unary_exp) => {
-                plus_unary_exp.build(program, my_ir_generator_info)
+            Expr::Binary(exp::BinaryOp::And, first_exp, second_exp, span) => {
+                build_short_circuit(&**first_exp, &**second_exp, program, my_ir_generator_info, true, *span)
+            }
+            Expr::Binary(exp::BinaryOp::Or, first_exp, second_exp, span) => {
+                build_short_circuit(&**first_exp, &**second_exp, program, my_ir_generator_info, false, *span)
             }
-            UnaryExp::MinusUnaryExp(unary_exp) => build_binary_from_buildables(
-                &Number::IntConst(0),
-                &**unary_exp,   //what can i say
+            Expr::Binary(op, first_exp, second_exp, _) => build_binary_from_buildables(
+                &**first_exp,
+                &**second_exp,
+                program,
+                my_ir_generator_info,
+                koopa_binary_op(*op),
+            ),
+            Expr::Unary(UnaryOp::Plus, exp, _) => exp.build(program, my_ir_generator_info),
+            Expr::Unary(UnaryOp::Minus, exp, span) => build_binary_from_buildables(
+                &Expr::Lit(0, *span),
+                &**exp,   //what can i say
                 program,
                 my_ir_generator_info,
                 BinaryOp::Sub,
             ),
-            UnaryExp::NotUnaryExp(unary_exp) => build_binary_from_buildables(
-                &**unary_exp,
-                &Number::IntConst(0),
+            Expr::Unary(UnaryOp::Not, exp, span) => build_binary_from_buildables(
+                &**exp,
+                &Expr::Lit(0, *span),
                 program,
                 my_ir_generator_info,
                 BinaryOp::Eq,
             ),
+            Expr::Call(ident, args, _) => {
+                let callee = my_ir_generator_info.function_table[&ident.content];
+                let mut arg_values = Vec::new();
+                for arg in args {
+                    arg.build(program, my_ir_generator_info);
+                    arg_values.push(my_ir_generator_info.curr_value.unwrap());
+                }
+                let call_inst = program
+                    .func_mut(my_ir_generator_info.curr_func.unwrap())
+                    .dfg_mut()
+                    .new_value()
+                    .call(callee, arg_values);
+                push_inst(program, my_ir_generator_info, call_inst);
+                my_ir_generator_info.curr_value = Some(call_inst);
+            }
+            Expr::Var(ident) => build_lval_value(ident, &[], program, my_ir_generator_info),
+            Expr::Index(ident, indices, _) => build_lval_value(ident, indices, program, my_ir_generator_info),
+            Expr::Lit(int, _) => {
+                let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
+                my_ir_generator_info.curr_value =
+                    Some(curr_func_data.dfg_mut().new_value().integer(*int));
+            }
         }
     }
 }
@@ -279,296 +368,202 @@ impl Buildable for Stmt {
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
+    ) {
         match &self {
-            Stmt::ReturnStmt(exp) => {
-                exp.build(program, my_ir_generator_info)?;
-                let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
-                let return_stmt = curr_func_data
+            Stmt::ReturnStmt(exp, _) => {
+                let return_value = match exp {
+                    Some(exp) => {
+                        exp.build(program, my_ir_generator_info);
+                        my_ir_generator_info.curr_value
+                    }
+                    None => None,
+                };
+                let return_stmt = program
+                    .func_mut(my_ir_generator_info.curr_func.unwrap())
                     .dfg_mut()
                     .new_value()
-                    .ret(my_ir_generator_info.curr_value);
-                curr_func_data
-                    .layout_mut()
-                    .bb_mut(my_ir_generator_info.curr_block.unwrap())
-                    .insts_mut()
-                    .extend([return_stmt]);
+                    .ret(return_value);
+                push_inst(program, my_ir_generator_info, return_stmt);
             }
-            Stmt::AssignStmt(lval, exp) => {
-                let LVal::IDENT(ident)=lval;
-                match my_ir_generator_info.curr_symbols.get(&ident.content).unwrap(){
-                    super::SymbolsEntry::Variable(_, _) => {
-                        //do nothing  continue
-                        
-                    },
-                    super::SymbolsEntry::Const(_, _) => {
-                        return Err(format!("Left Value should not exist in const expression! "));
-                    },
-                }
-                lval.build(program, my_ir_generator_info)?;
+            Stmt::AssignStmt(lval, exp, _) => {
+                lval.build(program, my_ir_generator_info);
                 let lval_ptr = my_ir_generator_info.curr_value.unwrap();
                 // Build RHS value.
-                exp.build(program, my_ir_generator_info)?;
+                exp.build(program, my_ir_generator_info);
                 let rhs_value = my_ir_generator_info.curr_value.unwrap();
                 // Assign the RHS value into the new variable.
-                let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
-                let dfg = curr_func_data.dfg_mut();
-                let store_inst = dfg.new_value().store(rhs_value, lval_ptr);
-                curr_func_data
-                    .layout_mut()
-                    .bb_mut(my_ir_generator_info.curr_block.unwrap())
-                    .insts_mut()
-                    .extend([lval_ptr, store_inst]);
+                let store_inst = program
+                    .func_mut(my_ir_generator_info.curr_func.unwrap())
+                    .dfg_mut()
+                    .new_value()
+                    .store(rhs_value, lval_ptr);
+                push_inst(program, my_ir_generator_info, store_inst);
             },
-        }
-        Ok(())
-    }
-}
-impl Buildable for PrimaryExp {
-    fn build(
-        &self,
-        program: &mut Program,
-        my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self {
-            PrimaryExp::BracedExp(exp) => exp.build(program, my_ir_generator_info),
-            PrimaryExp::Number(number) => number.build(program, my_ir_generator_info),
-            PrimaryExp::LVal(lval) =>  {
-                let LVal::IDENT(ident)=lval;
-                match my_ir_generator_info.curr_symbols.get(&ident.content).unwrap() {
-                    super::SymbolsEntry::Variable(_, _) => {
-                        lval.build(program, my_ir_generator_info)?;
-                        let ptr=my_ir_generator_info.curr_value.unwrap();
-                        let curr_func_data =
-                            program.func_mut(my_ir_generator_info.curr_func.unwrap());
-                        let dfg = curr_func_data.dfg_mut();
-                        let load_inst = dfg.new_value().load(ptr);
-                        my_ir_generator_info.curr_value = Some(load_inst);
-                        curr_func_data
-                            .layout_mut()
-                            .bb_mut(my_ir_generator_info.curr_block.unwrap())
-                            .insts_mut()
-                            .extend([load_inst]);
-                        Ok(())
-                    },
-                    super::SymbolsEntry::Const(_, _) => lval.build(program, my_ir_generator_info),
+            Stmt::ExpStmt(exp, _) => {
+                //表达式求值后丢弃结果
+                if let Some(exp) = exp {
+                    exp.build(program, my_ir_generator_info);
                 }
-            }
-        }
-    }
-}
-impl Buildable for LVal{
-    fn build(
-        &self,
-        program: &mut Program,
-        my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self {
-            //在遇到 LVal 时, 你应该从符号表中查询这个符号的值, 然后用查到的结果作为常量求值/IR 生成的结果
-            LVal::IDENT(ident) => 
-                match my_ir_generator_info.curr_symbols.get(&ident.content).unwrap() {
-                    crate::ir_builder::SymbolsEntry::Variable(type_name, ptr) => {
-                        if let Some(_) = my_ir_generator_info.tmp_constants {
-                            // Calculating constant expression
-                            return Err(format!("Left Value should not exist in const expression! "));
-                        }
-                        // Don't load it right now, because it may be used as a pointer.
-                        my_ir_generator_info.curr_value = *ptr;
-                        Ok(())
-                    },
-                    crate::ir_builder::SymbolsEntry::Const(type_name, val) => {
-                        if let Some(_) =my_ir_generator_info.tmp_constants{
-                            my_ir_generator_info.tmp_constants=Some((*val,123456));
-                            return Ok(());
-                        }
-                        my_ir_generator_info.curr_value=Some(
-                            program
-                                .func_mut(my_ir_generator_info.curr_func.unwrap())
-                                .dfg_mut()
-                                .new_value()
-                                .integer(*val),
-                        );
-                        return Ok(());
-                    },
             },
-        }
-    }
-}
-impl Buildable for Number {
-    fn build(
-        &self,
-        program: &mut Program,
-        my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self {
-            Number::IntConst(int) => {
-                if let Some(_) = my_ir_generator_info.tmp_constants {
-                    // Calculating constant expression
-                    my_ir_generator_info.tmp_constants = Some((*int, 233333));
-                    return Ok(());
+            Stmt::BlockStmt(block) => block.build(program, my_ir_generator_info),
+            Stmt::IfStmt(cond, then_stmt, else_stmt, _) => {
+                cond.build(program, my_ir_generator_info);
+                let cond_value = my_ir_generator_info.curr_value.unwrap();
+                let id = my_ir_generator_info.bb_count;
+                my_ir_generator_info.bb_count += 1;
+                let then_bb = new_bb(program, my_ir_generator_info, format!("%then_{}", id));
+                let end_bb = new_bb(program, my_ir_generator_info, format!("%end_{}", id));
+                let else_bb = match else_stmt {
+                    Some(_) => new_bb(program, my_ir_generator_info, format!("%else_{}", id)),
+                    None => end_bb,
+                };
+                let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
+                let branch_inst = curr_func_data
+                    .dfg_mut()
+                    .new_value()
+                    .branch(cond_value, then_bb, else_bb);
+                push_inst(program, my_ir_generator_info, branch_inst);
+                //then 分支
+                enter_bb(program, my_ir_generator_info, then_bb);
+                then_stmt.build(program, my_ir_generator_info);
+                jump_to(program, my_ir_generator_info, end_bb);
+                //else 分支
+                if let Some(else_stmt) = else_stmt {
+                    enter_bb(program, my_ir_generator_info, else_bb);
+                    else_stmt.build(program, my_ir_generator_info);
+                    jump_to(program, my_ir_generator_info, end_bb);
                 }
+                enter_bb(program, my_ir_generator_info, end_bb);
+            },
+            Stmt::WhileStmt(cond, body, _) => {
+                let id = my_ir_generator_info.bb_count;
+                my_ir_generator_info.bb_count += 1;
+                let entry_bb = new_bb(program, my_ir_generator_info, format!("%while_entry_{}", id));
+                let body_bb = new_bb(program, my_ir_generator_info, format!("%while_body_{}", id));
+                let end_bb = new_bb(program, my_ir_generator_info, format!("%while_end_{}", id));
+                jump_to(program, my_ir_generator_info, entry_bb);
+                //循环入口: 判断条件
+                enter_bb(program, my_ir_generator_info, entry_bb);
+                cond.build(program, my_ir_generator_info);
+                let cond_value = my_ir_generator_info.curr_value.unwrap();
                 let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
-                my_ir_generator_info.curr_value =
-                    Some(curr_func_data.dfg_mut().new_value().integer(*int));
-                Ok(())
-            }
-        }
-    }
-}
-impl Buildable for AddExp {
-    fn build(
-            &self,
-            program: &mut Program,
-            my_ir_generator_info: &mut MyIRGeneratorInfo,
-        ) -> Result<(), String> {
-        match self{
-            AddExp::BinaryAddExp(first_exp,second_exp)=>build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::Add),
-            AddExp::MulExp(exp) => {
-                exp.build(program, my_ir_generator_info)
+                let branch_inst = curr_func_data
+                    .dfg_mut()
+                    .new_value()
+                    .branch(cond_value, body_bb, end_bb);
+                push_inst(program, my_ir_generator_info, branch_inst);
+                //循环体
+                enter_bb(program, my_ir_generator_info, body_bb);
+                my_ir_generator_info.loop_stack.push(LoopInfo { entry: entry_bb, end: end_bb });
+                body.build(program, my_ir_generator_info);
+                my_ir_generator_info.loop_stack.pop();
+                jump_to(program, my_ir_generator_info, entry_bb);
+                enter_bb(program, my_ir_generator_info, end_bb);
             },
-            AddExp::BinarySubExp(first_exp,second_exp)=>build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::Sub),
-
-        }
-    }
-}
-impl Buildable for MulExp {
-    fn build(
-            &self,
-            program: &mut Program,
-            my_ir_generator_info: &mut MyIRGeneratorInfo,
-        ) -> Result<(), String> {
-        match self {
-            MulExp::UnaryExp(exp)=>{
-                exp.build(program, my_ir_generator_info)
-            }
-            MulExp::BinaryDivExp(first_exp,second_exp) => build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::Div),
-            MulExp::BinaryMulExp(first_exp,second_exp) => build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::Mul),
-            MulExp::BinaryModExp(first_exp,second_exp) => build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::Mod),
-
+            Stmt::BreakStmt(_) => {
+                let end_bb = my_ir_generator_info.loop_stack.last().unwrap().end;
+                jump_to(program, my_ir_generator_info, end_bb);
+            },
+            Stmt::ContinueStmt(_) => {
+                let entry_bb = my_ir_generator_info.loop_stack.last().unwrap().entry;
+                jump_to(program, my_ir_generator_info, entry_bb);
+            },
+            Stmt::Error(_) => unreachable!("checked units have no syntax errors"),
         }
     }
 }
-impl Buildable for RelExp {
+impl Buildable for LVal{
     fn build(
         &self,
         program: &mut Program,
         my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self{
-            RelExp::AddExp(exp) => exp.build(program, my_ir_generator_info),
-            RelExp::BinaryLtRelExp(first_exp, second_exp) => build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::Lt),
-            RelExp::BinaryGtRelExp(first_exp, second_exp) => build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::Gt),
-            RelExp::BinaryLeRelExp(first_exp, second_exp) => build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::Le),
-            RelExp::BinaryGeRelExp(first_exp, second_exp) => build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::Ge),
+    ) {
+        match self {
+            LVal::IDENT(ident) => build_lval(ident, &[], program, my_ir_generator_info),
+            LVal::ArrayElem(ident, indices, _) => build_lval(ident, indices, program, my_ir_generator_info),
         }
     }
 }
-impl Buildable for EqExp {
-    fn build(
-        &self,
-        program: &mut Program,
-        my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self{
-            EqExp::RelExp(exp) => exp.build(program, my_ir_generator_info),
-            EqExp::BinaryEqExp(first_exp, second_exp) => build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::Eq),
-            EqExp::BinaryNotEqExp(first_exp, second_exp) => build_binary_from_buildables(&**first_exp, second_exp, program, my_ir_generator_info, BinaryOp::NotEq),
-        }
+/// 计算左值的地址; 标量常量直接得到它的值.
+/// 没有下标时是变量本身, 否则逐维计算数组元素的地址
+fn build_lval(
+    ident: &IDENT,
+    indices: &[Expr],
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) {
+    //变量的地址; 先不 load, 因为它可能作为指针使用
+    let mut ptr = symbol_ptr(my_ir_generator_info, ident);
+    if indices.is_empty() {
+        my_ir_generator_info.curr_value = Some(ptr);
+        return;
     }
-}
-impl Buildable for LAndExp {
-    fn build(
-        &self,
-        program: &mut Program,
-        my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self{
-            LAndExp::EqExp(exp) => exp.build(program, my_ir_generator_info),
-            LAndExp::BinaryLAndExp(first_exp, second_exp) => {
-                build_binary_from_buildables(
-                    &**first_exp,
-                    &Number::IntConst(0),
-                    program,
-                    my_ir_generator_info,
-                    BinaryOp::NotEq,
-                )?;
-                let mut tmp1 = 0;
-                if let Some((tmp, _)) = my_ir_generator_info.tmp_constants {
-                    // Calculating constant expression
-                    tmp1 = tmp;
-                }
-                let bool1 = my_ir_generator_info.curr_value;
-                build_binary_from_buildables(
-                    &Number::IntConst(0),
-                    second_exp,
-                    program,
-                    my_ir_generator_info,
-                    BinaryOp::NotEq,
-                )?;
-                if let Some((tmp2, _)) = my_ir_generator_info.tmp_constants {
-                    // Calculating constant expression
-                    my_ir_generator_info.tmp_constants = Some((tmp1, tmp2));
-                }
-                let bool2 = my_ir_generator_info.curr_value;
-
-                build_binary_from_values(
-                    bool1,
-                    bool2,
-                    program,
-                    my_ir_generator_info,
-                    BinaryOp::And,
-                )
-            }
-        }
+    //逐维计算元素的地址, 数组参数 (存放的是指针) 需要先取出指针, 再用 getptr 计算第一维
+    let is_param = matches!(
+        value_type(program, my_ir_generator_info, ptr).kind(),
+        TypeKind::Pointer(base) if matches!(base.kind(), TypeKind::Pointer(_))
+    );
+    if is_param {
+        ptr = program
+            .func_mut(my_ir_generator_info.curr_func.unwrap())
+            .dfg_mut()
+            .new_value()
+            .load(ptr);
+        push_inst(program, my_ir_generator_info, ptr);
     }
+    for (i, index) in indices.iter().enumerate() {
+        index.build(program, my_ir_generator_info);
+        let index_value = my_ir_generator_info.curr_value.unwrap();
+        let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
+        ptr = if is_param && i == 0 {
+            curr_func_data.dfg_mut().new_value().get_ptr(ptr, index_value)
+        } else {
+            curr_func_data.dfg_mut().new_value().get_elem_ptr(ptr, index_value)
+        };
+        push_inst(program, my_ir_generator_info, ptr);
+    }
+    my_ir_generator_info.curr_value = Some(ptr);
 }
-impl Buildable for LOrExp {
-    fn build(
-        &self,
-        program: &mut Program,
-        my_ir_generator_info: &mut MyIRGeneratorInfo,
-    ) -> Result<(), String> {
-        match self{
-            LOrExp::LAndExp(exp) => exp.build(program, my_ir_generator_info),
-            LOrExp::BinaryLOrExp(first_exp, second_exp) => {
-                build_binary_from_buildables(
-                    &**first_exp,
-                    &Number::IntConst(0),
-                    program,
-                    my_ir_generator_info,
-                    BinaryOp::NotEq,
-                )?;
-                let mut tmp1 = 0;
-                if let Some((tmp, _)) = my_ir_generator_info.tmp_constants {
-                    // Calculating constant expression
-                    tmp1 = tmp;
-                }
-                let bool1 = my_ir_generator_info.curr_value;
 
-                build_binary_from_buildables(
-                    &Number::IntConst(0),
-                    second_exp,
-                    program,
-                    my_ir_generator_info,
-                    BinaryOp::NotEq,
-                )?;
-                if let Some((tmp2, _)) = my_ir_generator_info.tmp_constants {
-                    // Calculating constant expression
-                    my_ir_generator_info.tmp_constants = Some((tmp1, tmp2));
-                }
-                let bool2 = my_ir_generator_info.curr_value;
+/// 左值作为右值使用: 取出变量的值, 数组作为值使用时退化为指向第一个元素的指针
+fn build_lval_value(
+    ident: &IDENT,
+    indices: &[Expr],
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+) {
+    build_lval(ident, indices, program, my_ir_generator_info);
+    let ptr = my_ir_generator_info.curr_value.unwrap();
+    let is_array = is_array_pointer(&value_type(program, my_ir_generator_info, ptr));
+    let dfg = program.func_mut(my_ir_generator_info.curr_func.unwrap()).dfg_mut();
+    let load_inst = if is_array {
+        let zero = dfg.new_value().integer(0);
+        dfg.new_value().get_elem_ptr(ptr, zero)
+    } else {
+        dfg.new_value().load(ptr)
+    };
+    my_ir_generator_info.curr_value = Some(load_inst);
+    push_inst(program, my_ir_generator_info, load_inst);
+}
 
-                build_binary_from_values(
-                    bool1,
-                    bool2,
-                    program,
-                    my_ir_generator_info,
-                    BinaryOp::Or,
-                )
-            }
-        }
+/// 把 AST 中的运算符转换成 Koopa IR 的运算符, && 与 || 需要短路求值, 不在这里处理
+fn koopa_binary_op(op: exp::BinaryOp) -> BinaryOp {
+    match op {
+        exp::BinaryOp::Add => BinaryOp::Add,
+        exp::BinaryOp::Sub => BinaryOp::Sub,
+        exp::BinaryOp::Mul => BinaryOp::Mul,
+        exp::BinaryOp::Div => BinaryOp::Div,
+        exp::BinaryOp::Mod => BinaryOp::Mod,
+        exp::BinaryOp::Lt => BinaryOp::Lt,
+        exp::BinaryOp::Gt => BinaryOp::Gt,
+        exp::BinaryOp::Le => BinaryOp::Le,
+        exp::BinaryOp::Ge => BinaryOp::Ge,
+        exp::BinaryOp::Eq => BinaryOp::Eq,
+        exp::BinaryOp::NotEq => BinaryOp::NotEq,
+        exp::BinaryOp::And | exp::BinaryOp::Or => unreachable!(),
     }
 }
+
 //不懂-。-
 fn build_binary_from_buildables(
     first_exp: &dyn Buildable,
@@ -576,22 +571,13 @@ fn build_binary_from_buildables(
     program: &mut Program,
     my_ir_generator_info: &mut MyIRGeneratorInfo,
     binary_op: koopa::ir::BinaryOp,
-) -> Result<(), String> {
-    let mut tmp1=0;
-    first_exp.build(program, my_ir_generator_info)?;
+) {
+    first_exp.build(program, my_ir_generator_info);
     let first_value = my_ir_generator_info
         .curr_value;
-    if let Some((tmp, _)) = my_ir_generator_info.tmp_constants {
-        // Calculating constant expression
-        tmp1 = tmp;
-    }
-    second_exp.build(program, my_ir_generator_info)?;
+    second_exp.build(program, my_ir_generator_info);
     let second_value = my_ir_generator_info
         .curr_value;
-    if let Some((tmp2, _)) = my_ir_generator_info.tmp_constants {
-        // Calculating constant expression
-        my_ir_generator_info.tmp_constants = Some((tmp1, tmp2));
-    }
     build_binary_from_values(
         first_value,
         second_value,
@@ -601,6 +587,84 @@ fn build_binary_from_buildables(
     )
 }
 
+/// 短路求值的 && 与 ||: 左边的值已经能决定结果时不再计算右边.
+///
+/// 结果先存入一个临时变量, 右边只在需要时才求值:
+/// ```text
+///   %result = alloc i32
+///   store (first != 0), %result
+///   br (first != 0), %land_rhs, %land_end    // || 的两个分支相反
+/// %land_rhs:
+///   store (second != 0), %result
+///   jump %land_end
+/// %land_end:
+///   load %result
+/// ```
+fn build_short_circuit(
+    first_exp: &dyn Buildable,
+    second_exp: &dyn Buildable,
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+    is_and: bool,
+    span: Span,
+) {
+    let prefix = if is_and { "land" } else { "lor" };
+    let id = my_ir_generator_info.bb_count;
+    my_ir_generator_info.bb_count += 1;
+    let rhs_bb = new_bb(program, my_ir_generator_info, format!("%{}_rhs_{}", prefix, id));
+    let end_bb = new_bb(program, my_ir_generator_info, format!("%{}_end_{}", prefix, id));
+    let result = program
+        .func_mut(my_ir_generator_info.curr_func.unwrap())
+        .dfg_mut()
+        .new_value()
+        .alloc(Type::get_i32());
+    push_inst(program, my_ir_generator_info, result);
+
+    build_binary_from_buildables(
+        first_exp,
+        &Expr::Lit(0, span),
+        program,
+        my_ir_generator_info,
+        BinaryOp::NotEq,
+    );
+    let bool1 = my_ir_generator_info.curr_value.unwrap();
+    let dfg = program.func_mut(my_ir_generator_info.curr_func.unwrap()).dfg_mut();
+    let store_inst = dfg.new_value().store(bool1, result);
+    let branch_inst = if is_and {
+        dfg.new_value().branch(bool1, rhs_bb, end_bb)
+    } else {
+        dfg.new_value().branch(bool1, end_bb, rhs_bb)
+    };
+    push_inst(program, my_ir_generator_info, store_inst);
+    push_inst(program, my_ir_generator_info, branch_inst);
+
+    enter_bb(program, my_ir_generator_info, rhs_bb);
+    build_binary_from_buildables(
+        second_exp,
+        &Expr::Lit(0, span),
+        program,
+        my_ir_generator_info,
+        BinaryOp::NotEq,
+    );
+    let bool2 = my_ir_generator_info.curr_value.unwrap();
+    let store_inst = program
+        .func_mut(my_ir_generator_info.curr_func.unwrap())
+        .dfg_mut()
+        .new_value()
+        .store(bool2, result);
+    push_inst(program, my_ir_generator_info, store_inst);
+    jump_to(program, my_ir_generator_info, end_bb);
+
+    enter_bb(program, my_ir_generator_info, end_bb);
+    let load_inst = program
+        .func_mut(my_ir_generator_info.curr_func.unwrap())
+        .dfg_mut()
+        .new_value()
+        .load(result);
+    push_inst(program, my_ir_generator_info, load_inst);
+    my_ir_generator_info.curr_value = Some(load_inst);
+}
+
 //用二元表达式表示一元表达式
 fn build_binary_from_values(
     first_value: Option<koopa::ir::Value>,
@@ -608,73 +672,193 @@ fn build_binary_from_values(
     program: &mut Program,
     my_ir_generator_info: &mut MyIRGeneratorInfo,
     binary_op: koopa::ir::BinaryOp,
-) -> Result<(), String> {
-    if let Some((tmp1, tmp2)) = my_ir_generator_info.tmp_constants {
-        // Calculating constant expression
-        match binary_op {
-            BinaryOp::NotEq => {
-                my_ir_generator_info.tmp_constants = Some(((tmp1 != tmp2) as i32, 233333))
-            }
-            BinaryOp::Eq => {
-                my_ir_generator_info.tmp_constants = Some(((tmp1 == tmp2) as i32, 233333))
-            }
-            BinaryOp::Gt => {
-                my_ir_generator_info.tmp_constants = Some(((tmp1 > tmp2) as i32, 233333))
-            }
-            BinaryOp::Lt => {
-                my_ir_generator_info.tmp_constants = Some(((tmp1 < tmp2) as i32, 233333))
-            }
-            BinaryOp::Ge => {
-                my_ir_generator_info.tmp_constants = Some(((tmp1 >= tmp2) as i32, 233333))
-            }
-            BinaryOp::Le => {
-                my_ir_generator_info.tmp_constants = Some(((tmp1 <= tmp2) as i32, 233333))
-            }
-            BinaryOp::Add => {
-                my_ir_generator_info.tmp_constants = Some((tmp1 + tmp2, 233333))
-            }
-            BinaryOp::Sub => {
-                my_ir_generator_info.tmp_constants = Some((tmp1 - tmp2, 233333))
-            }
-            BinaryOp::Mul => {
-                my_ir_generator_info.tmp_constants = Some((tmp1 * tmp2, 233333))
-            }
-            BinaryOp::Div => {
-                my_ir_generator_info.tmp_constants = Some((tmp1 / tmp2, 233333))
-            }
-            BinaryOp::Mod => {
-                my_ir_generator_info.tmp_constants = Some((tmp1 % tmp2, 233333))
-            }
-            BinaryOp::And => {
-                my_ir_generator_info.tmp_constants = Some((tmp1 & tmp2, 233333))
-            }
-            BinaryOp::Or => {
-                my_ir_generator_info.tmp_constants = Some((tmp1 | tmp2, 233333))
-            }
-            BinaryOp::Xor => {
-                my_ir_generator_info.tmp_constants = Some((tmp1 ^ tmp2, 233333))
-            }
-            BinaryOp::Shl => {
-                my_ir_generator_info.tmp_constants = Some((tmp1 << tmp2, 233333))
-            }
-            BinaryOp::Shr => todo!(), // SysY has no left shift or right shift.
-            BinaryOp::Sar => {
-                my_ir_generator_info.tmp_constants = Some((tmp1 >> tmp2, 233333))
-            }
-        }
-        return Ok(());
+) {
+    let new_value = program
+        .func_mut(my_ir_generator_info.curr_func.unwrap())
+        .dfg_mut()
+        .new_value()
+        .binary(binary_op, first_value.unwrap(), second_value.unwrap());
+    push_inst(program, my_ir_generator_info, new_value);
+    my_ir_generator_info.curr_value = Some(new_value);
+}
+
+
+/// 创建一个新的基本块, 此时还未加入函数的 layout
+fn new_bb(
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+    name: String,
+) -> BasicBlock {
+    program
+        .func_mut(my_ir_generator_info.curr_func.unwrap())
+        .dfg_mut()
+        .new_bb()
+        .basic_block(Some(name))
+}
+
+/// 将基本块加入 layout, 之后的指令都追加到这个块中
+fn enter_bb(
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+    bb: BasicBlock,
+) {
+    program
+        .func_mut(my_ir_generator_info.curr_func.unwrap())
+        .layout_mut()
+        .bbs_mut()
+        .extend([bb]);
+    my_ir_generator_info.curr_block = Some(bb);
+}
+
+/// 向当前基本块末尾追加一条指令
+fn push_inst(program: &mut Program, my_ir_generator_info: &mut MyIRGeneratorInfo, inst: Value) {
+    program
+        .func_mut(my_ir_generator_info.curr_func.unwrap())
+        .layout_mut()
+        .bb_mut(my_ir_generator_info.curr_block.unwrap())
+        .insts_mut()
+        .extend([inst]);
+}
+
+/// 当前基本块是否已经以 ret/br/jump 结尾
+fn is_curr_bb_terminated(program: &Program, my_ir_generator_info: &MyIRGeneratorInfo) -> bool {
+    let curr_func_data = program.func(my_ir_generator_info.curr_func.unwrap());
+    let last_inst = curr_func_data
+        .layout()
+        .bbs()
+        .node(&my_ir_generator_info.curr_block.unwrap())
+        .and_then(|node| node.insts().back_key().copied());
+    match last_inst {
+        Some(inst) => matches!(
+            curr_func_data.dfg().value(inst).kind(),
+            ValueKind::Return(_) | ValueKind::Branch(_) | ValueKind::Jump(_)
+        ),
+        None => false,
     }
+}
+
+/// 若当前基本块还没有结束, 跳转到目标基本块
+fn jump_to(program: &mut Program, my_ir_generator_info: &mut MyIRGeneratorInfo, target: BasicBlock) {
+    if is_curr_bb_terminated(program, my_ir_generator_info) {
+        return;
+    }
+    let jump_inst = program
+        .func_mut(my_ir_generator_info.curr_func.unwrap())
+        .dfg_mut()
+        .new_value()
+        .jump(target);
+    push_inst(program, my_ir_generator_info, jump_inst);
+}
+
+/// 记录定义的地址, 之后对它的使用通过语义分析时分配的编号找到这个地址
+fn define_symbol(my_ir_generator_info: &mut MyIRGeneratorInfo, ident: &IDENT, ptr: Value) {
+    let id = ident.symbol.expect("definitions are numbered by semantic analysis");
+    my_ir_generator_info.symbol_ptrs.insert(id, ptr);
+}
+/// 名字引用的定义的地址, 语义分析时已经解析到了定义
+fn symbol_ptr(my_ir_generator_info: &MyIRGeneratorInfo, ident: &IDENT) -> Value {
+    let id = ident.symbol.expect("names are resolved by semantic analysis");
+    my_ir_generator_info.symbol_ptrs[&id]
+}
+
+/// 语义分析之后, 常量表达式都已经折叠成了字面量
+fn lit_value(exp: &Expr) -> i32 {
+    match exp {
+        Expr::Lit(value, _) => *value,
+        _ => unreachable!(),
+    }
+}
+fn const_value(const_exp: &ConstExp) -> i32 {
+    match const_exp {
+        ConstExp::Exp(exp) => lit_value(exp),
+    }
+}
+
+/// 数组每一维的长度
+fn const_dims(dims: &[ConstExp]) -> Vec<usize> {
+    dims.iter().map(|dim| const_value(dim) as usize).collect()
+}
+
+/// 由各维长度得到数组类型, 没有维度时为 i32
+pub(super) fn array_type(dims: &[usize]) -> Type {
+    dims.iter()
+        .rev()
+        .fold(Type::get_i32(), |base, &len| Type::get_array(base, len))
+}
+
+/// 查询值的类型, 全局变量不在函数的 dfg 中
+fn value_type(program: &Program, my_ir_generator_info: &MyIRGeneratorInfo, value: Value) -> Type {
+    if value.is_global() {
+        program.borrow_value(value).ty().clone()
+    } else {
+        program
+            .func(my_ir_generator_info.curr_func.unwrap())
+            .dfg()
+            .value(value)
+            .ty()
+            .clone()
+    }
+}
+
+/// 是否是指向数组的指针
+fn is_array_pointer(ty: &Type) -> bool {
+    matches!(ty.kind(), TypeKind::Pointer(base) if matches!(base.kind(), TypeKind::Array(_, _)))
+}
+
+/// 由按行优先排列的常量生成全局变量的初始值, 全为 0 的部分使用 zeroinit
+fn build_aggregate(program: &mut Program, dims: &[usize], values: &[i32]) -> Value {
+    if dims.is_empty() {
+        return program.new_value().integer(values[0]);
+    }
+    if values.iter().all(|&value| value == 0) {
+        return program.new_value().zero_init(array_type(dims));
+    }
+    let stride = values.len() / dims[0];
+    let elems = values
+        .chunks(stride)
+        .map(|chunk| build_aggregate(program, &dims[1..], chunk))
+        .collect();
+    program.new_value().aggregate(elems)
+}
+
+/// 在当前函数中为局部变量分配空间
+fn new_local_var(
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+    name: &str,
+    ty: Type,
+) -> Value {
+    let name = my_ir_generator_info.unique_name(name);
     let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
-    let new_value =
-        curr_func_data
+    let var_ptr = curr_func_data.dfg_mut().new_value().alloc(ty);
+    curr_func_data.dfg_mut().set_value_name(var_ptr, Some(name));
+    push_inst(program, my_ir_generator_info, var_ptr);
+    var_ptr
+}
+
+/// 把按行优先排列的初始值逐个存入局部变量
+fn store_init_values(
+    program: &mut Program,
+    my_ir_generator_info: &mut MyIRGeneratorInfo,
+    ptr: Value,
+    dims: &[usize],
+    values: &[Value],
+) {
+    if dims.is_empty() {
+        let store_inst = program
+            .func_mut(my_ir_generator_info.curr_func.unwrap())
             .dfg_mut()
             .new_value()
-            .binary(binary_op, first_value.unwrap(), second_value.unwrap());
-    curr_func_data
-        .layout_mut()
-        .bb_mut(my_ir_generator_info.curr_block.unwrap())
-        .insts_mut()
-        .extend([new_value]);
-    my_ir_generator_info.curr_value = Some(new_value);
-    Ok(())
+            .store(values[0], ptr);
+        push_inst(program, my_ir_generator_info, store_inst);
+        return;
+    }
+    let stride = values.len() / dims[0];
+    for (i, chunk) in values.chunks(stride).enumerate() {
+        let curr_func_data = program.func_mut(my_ir_generator_info.curr_func.unwrap());
+        let index = curr_func_data.dfg_mut().new_value().integer(i as i32);
+        let elem_ptr = curr_func_data.dfg_mut().new_value().get_elem_ptr(ptr, index);
+        push_inst(program, my_ir_generator_info, elem_ptr);
+        store_init_values(program, my_ir_generator_info, elem_ptr, &dims[1..], chunk);
+    }
 }
@@ -1,47 +1,224 @@
 //! This module is the frontend of my compiler.
 //! It converts the C code into Koopa IR.
 
+#[allow(clippy::module_inception)]
 mod ir_builder;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::ast::statements::*;
-use ir_builder::Buildable;
+use crate::semantic::{runtime_lib, CheckedUnit, ExpType};
+use ir_builder::{array_type, Buildable};
 use koopa::ir::entities::{BasicBlock, Function}; // Koopa IR builder
-use koopa::ir::{Program, Value,TypeKind}; // All the symbol defined in the AST
+use koopa::ir::{FunctionData, Program, Type, Value}; // All the symbol defined in the AST
 
-pub fn generate_ir(comp_unit: &CompUnit) -> Result<Program, String> {
+pub fn generate_ir(checked_unit: &CheckedUnit) -> Program {
     let mut program = Program::new();
     let mut my_ir_generator_info = MyIRGeneratorInfo {
         curr_block: None,
         curr_func: None,
         curr_value:None,
-        curr_symbols:HashMap::new(),
-        curr_type:None,
-        tmp_constants: None,
+        symbol_ptrs:HashMap::new(),
+        used_names:HashSet::new(),
+        function_table:HashMap::new(),
+        bb_count: 0,
+        loop_stack: Vec::new(),
     };
-    comp_unit.build(&mut program, &mut my_ir_generator_info)?;
-    println!("{:#?}",my_ir_generator_info.curr_symbols);
-    Ok(program)
+    declare_runtime_lib(&mut program, &mut my_ir_generator_info);
+    reserve_global_names(checked_unit.comp_unit(), &mut my_ir_generator_info);
+    checked_unit.comp_unit().build(&mut program, &mut my_ir_generator_info);
+    program
+}
+
+/// 预先声明 SysY 运行时库 (libsysy) 中的函数, 生成的 IR 中对应 decl 语句
+fn declare_runtime_lib(program: &mut Program, my_ir_generator_info: &mut MyIRGeneratorInfo) {
+    for (name, signature) in runtime_lib() {
+        let params = signature.params.iter().map(koopa_type).collect();
+        let ret = koopa_type(&signature.return_type);
+        let func = program.new_func(FunctionData::new_decl(format!("@{}", name), params, ret));
+        my_ir_generator_info.function_table.insert(name.to_string(), func);
+    }
+}
+
+/// 全局变量直接使用源代码中的名字 (同时也是汇编中的标签), 全局作用域中不会重名.
+/// 局部变量与参数的名字不能与它们相同, 因此在生成 IR 之前先登记
+fn reserve_global_names(comp_unit: &CompUnit, my_ir_generator_info: &mut MyIRGeneratorInfo) {
+    for item in &comp_unit.items {
+        let idents: Vec<&IDENT> = match item {
+            GlobalItem::Decl(Decl::ConstDecl(ConstDecl::ConstDecl(_, const_defs, _))) => const_defs
+                .iter()
+                .map(|ConstDef::ConstDef(ident, ..)| ident)
+                .collect(),
+            GlobalItem::Decl(Decl::VarDecl(VarDecl::VarDecl(_, var_defs, _))) => var_defs
+                .iter()
+                .map(|(VarDef::VarDef(ident, ..) | VarDef::IDENT(ident, ..))| ident)
+                .collect(),
+            GlobalItem::FuncDef(_) | GlobalItem::Error(_) => continue,
+        };
+        for ident in idents {
+            my_ir_generator_info.used_names.insert(format!("@{}", ident.content));
+        }
+    }
+}
+
+/// 语义分析中的类型对应的 Koopa 类型
+fn koopa_type(exp_type: &ExpType) -> Type {
+    match exp_type {
+        ExpType::Int => Type::get_i32(),
+        ExpType::Void => Type::get_unit(),
+        ExpType::Pointer(dims) => Type::get_pointer(array_type(dims)),
+    }
 }
 
 pub struct MyIRGeneratorInfo {
     curr_block: Option<BasicBlock>, // Current block
     curr_func: Option<Function>,    // Current function
     curr_value:Option<Value>,       // Current return Value
-    curr_symbols:HashMap<String,SymbolsEntry>, //符号表
-    curr_type:Option<TypeKind>,
-    tmp_constants: Option<(i32, i32)>, // Temporary constant
+    symbol_ptrs:HashMap<SymbolId,Value>,       //变量与常量数组的地址, 按语义分析时分配的编号查找
+    used_names:HashSet<String>,                //全局变量的名字, 以及已经分配出去的局部变量名
+    function_table:HashMap<String,Function>,   //已定义的函数
+    bb_count: usize,                   // 已创建的基本块编号, 保证块名唯一
+    loop_stack: Vec<LoopInfo>,         // 当前所在的循环, 最内层在栈顶
+}
+impl MyIRGeneratorInfo {
+    //为变量分配一个不重复的 Koopa 名字, 形如 @x_1, @x_2
+    fn unique_name(&mut self, name: &str) -> String {
+        (1..)
+            .map(|i| format!("@{}_{}", name, i))
+            .find(|candidate| self.used_names.insert(candidate.clone()))
+            .unwrap()
+    }
+    //参数的名字, 与全局变量重名时改用不重复的名字
+    fn param_name(&mut self, name: &str) -> String {
+        let param_name = format!("@{}", name);
+        if self.used_names.contains(&param_name) {
+            self.unique_name(name)
+        } else {
+            param_name
+        }
+    }
 }
 
-pub enum SymbolsEntry{
-    Variable(TypeKind,Option<Value>),
-    Const(TypeKind,i32),
+/// 循环的入口块与出口块, continue 跳转到入口, break 跳转到出口
+pub struct LoopInfo {
+    entry: BasicBlock,
+    end: BasicBlock,
 }
-impl std::fmt::Debug for SymbolsEntry {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            SymbolsEntry::Variable(tk, v) => write!(f, "Variable {}: {:?}", tk, v),
-            SymbolsEntry::Const(tk, v) => write!(f, "Constant {}: {:?}", tk, v),
-        }
+
+#[cfg(test)]
+mod tests {
+    use crate::{generate_koopa, parse};
+
+    //生成函数 func 的 Koopa IR, 按基本块切分成 (标签, 块内指令)
+    fn function_blocks(source: &str, func: &str) -> Vec<(String, String)> {
+        let mut koopa = Vec::new();
+        generate_koopa(&super::generate_ir(&parse(source).unwrap()), &mut koopa).unwrap();
+        let koopa = String::from_utf8(koopa).unwrap();
+        let start = koopa.find(&format!("fun @{}(", func)).unwrap();
+        let end = koopa[start..].find("\n}\n").map_or(koopa.len(), |end| start + end);
+        koopa[start..end]
+            .split("\n\n")
+            .map(|block| {
+                let block = block.trim_start_matches(|c| c != '%');
+                let (label, insts) = block.split_once(":\n").unwrap();
+                (label.to_string(), insts.to_string())
+            })
+            .collect()
     }
-}
\ No newline at end of file
+    fn main_blocks(source: &str) -> Vec<(String, String)> {
+        function_blocks(source, "main")
+    }
+    //包含 pattern 的基本块的标签
+    fn blocks_containing<'a>(blocks: &'a [(String, String)], pattern: &str) -> Vec<&'a str> {
+        blocks
+            .iter()
+            .filter(|(_, insts)| insts.contains(pattern))
+            .map(|(label, _)| label.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn and_evaluates_rhs_only_when_lhs_is_true() {
+        let blocks = main_blocks(
+            "int main() { int a = getint(); int b = getint(); if (b != 0 && a / b > 1) { return 1; } return 0; }",
+        );
+        assert_eq!(blocks_containing(&blocks, "div"), ["%land_rhs_0"]);
+        let (_, entry) = &blocks[0];
+        assert!(entry.ends_with(", %land_rhs_0, %land_end_0"), "{}", entry);
+    }
+
+    #[test]
+    fn or_skips_rhs_when_lhs_is_true() {
+        let blocks = main_blocks(
+            "int main() { int a = getint(); int b = getint(); if (b == 0 || a / b > 1) { return 1; } return 0; }",
+        );
+        assert_eq!(blocks_containing(&blocks, "div"), ["%lor_rhs_0"]);
+        let (_, entry) = &blocks[0];
+        assert!(entry.ends_with(", %lor_end_0, %lor_rhs_0"), "{}", entry);
+    }
+
+    #[test]
+    fn statements_after_return_are_dropped() {
+        let source = "void v(int x) { if (x) { return; putint(1); } putint(x); } int main() { v(1); return 0; putint(2); }";
+        let blocks = function_blocks(source, "v");
+        assert!(blocks.contains(&("%then_0".to_string(), "  ret".to_string())), "{:?}", blocks);
+        assert!(blocks_containing(&blocks, "putint(1)").is_empty());
+        assert!(blocks_containing(&main_blocks(source), "putint").is_empty());
+    }
+
+    #[test]
+    fn statements_after_break_and_continue_are_dropped() {
+        let source = "int main() { int x = getint(); while (x) { if (x) { break; x = 5; } x = x + 1; continue; x = 7; } return x; }";
+        let blocks = main_blocks(source);
+        assert!(blocks.contains(&("%then_1".to_string(), "  jump %while_end_0".to_string())), "{:?}", blocks);
+        assert!(blocks_containing(&blocks, "store 5").is_empty());
+        assert!(blocks_containing(&blocks, "store 7").is_empty());
+        let (_, end_1) = blocks.iter().find(|(label, _)| label == "%end_1").unwrap();
+        assert!(end_1.ends_with("  jump %while_entry_0"), "{}", end_1);
+    }
+
+    #[test]
+    fn functions_get_an_implicit_return() {
+        //void 函数补上 ret, int 函数补上 ret 0
+        let source = "void v() { putint(1); } int f(int x) { if (x) { return 1; } } int main() { v(); return f(0); }";
+        let (_, v_entry) = function_blocks(source, "v").pop().unwrap();
+        assert_eq!(v_entry, "  call @putint(1)\n  ret");
+        let (label, f_end) = function_blocks(source, "f").pop().unwrap();
+        assert_eq!((label.as_str(), f_end.as_str()), ("%end_0", "  ret 0"));
+        //main 中已经有 return, 不再补 ret
+        let (_, main_entry) = main_blocks(source).pop().unwrap();
+        assert_eq!(main_entry.matches("ret").count(), 1);
+    }
+
+    #[test]
+    fn infinite_loop_body_gets_an_implicit_return_after_the_loop() {
+        let source = "int g() { while (1) { return 3; } } int main() { return g(); }";
+        let blocks = function_blocks(source, "g");
+        assert_eq!(
+            blocks.iter().map(|(label, insts)| (label.as_str(), insts.as_str())).collect::<Vec<_>>(),
+            [
+                ("%entry", "  jump %while_entry_0"),
+                ("%while_entry_0", "  br 1, %while_body_0, %while_end_0"),
+                ("%while_body_0", "  ret 3"),
+                ("%while_end_0", "  ret 0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn break_and_continue_target_the_innermost_loop() {
+        let source = "int main() { int i = 0; while (i < 3) { int j = 0; \
+            while (j < 3) { if (j == 1) { break; } j = j + 1; continue; } \
+            if (i == 1) { continue; } i = i + 1; break; } return i; }";
+        let blocks = main_blocks(source);
+        let last_inst = |label: &str| {
+            let (_, insts) = blocks.iter().find(|(block, _)| block == label).unwrap();
+            insts.lines().last().unwrap().trim().to_string()
+        };
+        //内层循环中的 break 与 continue
+        assert_eq!(last_inst("%then_2"), "jump %while_end_1");
+        assert_eq!(last_inst("%end_2"), "jump %while_entry_1");
+        //离开内层循环之后, 跳转的是外层循环
+        assert_eq!(last_inst("%then_3"), "jump %while_entry_0");
+        assert_eq!(last_inst("%end_3"), "jump %while_end_0");
+    }
+}
@@ -0,0 +1,273 @@
+//! A compiler from SysY to Koopa IR and RISC-V assembly.
+//! 编译分为几个阶段, 每个阶段都可以单独调用:
+//! [`parse`] 得到经过语义检查的 AST, [`generate_ir`] 生成 Koopa IR,
+//! [`generate_koopa`] 与 [`generate_riscv_asm`] 把 IR 输出成文本; [`compile`] 一次完成所有阶段.
+
+pub mod ast;
+pub mod semantic;
+pub mod ir_builder;
+pub mod asm_builder;
+pub mod error;
+pub mod optimizer;
+use std::io::Write;
+
+use ast::statements::CompUnit;
+use error::{CompileError, Warning};
+use semantic::CheckedUnit;
+use koopa::back::KoopaGenerator;
+use koopa::ir::Program;
+use lalrpop_util::lalrpop_mod;
+use optimizer::OptLevel;
+
+pub use asm_builder::generate_riscv_asm;
+pub use ir_builder::generate_ir;
+
+// 引用 lalrpop 生成的解析器
+// 因为我们刚刚创建了 sysy.lalrpop, 所以模块名是 sysy
+lalrpop_mod!(#[allow(clippy::all)] sysy);
+
+/// 输出的内容
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Emit {
+    Koopa,
+    #[default]
+    Riscv,
+}
+
+/// 编译选项
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Options {
+    pub emit: Emit,
+    pub opt_level: OptLevel,
+}
+
+/// 解析源代码并做语义检查, 得到的结果可以直接交给 [`generate_ir`]. 返回所有的语法或语义错误
+pub fn parse(source: &str) -> Result<CheckedUnit, Vec<CompileError>> {
+    parse_with_warnings(source, &mut Vec::new())
+}
+
+/// 与 [`parse`] 相同, 发现的警告追加到 warnings 中
+pub fn parse_with_warnings(source: &str, warnings: &mut Vec<Warning>) -> Result<CheckedUnit, Vec<CompileError>> {
+    let (comp_unit, syntax_errors) = parse_syntax(source)?;
+    check(comp_unit, syntax_errors, warnings)
+}
+
+/// 只做语法分析. 解析器从语法错误中恢复时, 返回带有 Error 节点的 AST 与恢复过的错误;
+/// 无法恢复时只返回错误
+fn parse_syntax(source: &str) -> Result<(CompUnit, Vec<CompileError>), Vec<CompileError>> {
+    // 解析时遇到的语法错误会被记录下来, 解析器恢复后继续, 最后一起报告
+    let mut recovered_errors = Vec::new();
+    let result = sysy::CompUnitParser::new().parse(&mut recovered_errors, source);
+    let mut syntax_errors: Vec<CompileError> = recovered_errors
+        .into_iter()
+        .map(|recovery| CompileError::from_parse_error(recovery.error, source))
+        .collect();
+    match result {
+        Ok(comp_unit) => Ok((comp_unit, syntax_errors)),
+        Err(err) => {
+            syntax_errors.push(CompileError::from_parse_error(err, source));
+            Err(syntax_errors)
+        }
+    }
+}
+
+/// 语义检查. 有语法错误时仍然检查恢复后的 AST (跳过 Error 节点), 与语法错误按位置合并后一起报告,
+/// 这时不会得到可以生成 IR 的结果
+fn check(
+    comp_unit: CompUnit,
+    mut syntax_errors: Vec<CompileError>,
+    warnings: &mut Vec<Warning>,
+) -> Result<CheckedUnit, Vec<CompileError>> {
+    let result = semantic::analyze(comp_unit, warnings);
+    if syntax_errors.is_empty() {
+        return result;
+    }
+    if let Err(mut semantic_errors) = result {
+        syntax_errors.append(&mut semantic_errors);
+        syntax_errors.sort_by_key(|err| err.span().start);
+    }
+    Err(syntax_errors)
+}
+
+/// 把 IR 输出成 Koopa IR 文本
+pub fn generate_koopa(program: &Program, output: impl Write) -> std::io::Result<()> {
+    KoopaGenerator::new(output).generate_on(program)
+}
+
+/// 调试用的中间结果, 不为 None 的项在对应的阶段完成后写入
+#[derive(Default)]
+pub struct Dumps<'a> {
+    pub ast: Option<&'a mut dyn Write>,     //语法分析得到的 AST (常量还没有折叠)
+    pub symbols: Option<&'a mut dyn Write>, //语义分析得到的函数与各作用域中的符号
+    pub ir: Option<&'a mut dyn Write>,      //优化之后的 Koopa IR
+    pub asm: Option<&'a mut dyn Write>,     //RISC-V 汇编, 与 options.emit 无关
+}
+
+/// 把源代码编译成 options.emit 指定的文本, 写入 output. 发现的警告 (不论是否出错) 追加到 warnings 中,
+/// 由调用者决定是否报告
+pub fn compile(
+    source: &str,
+    options: &Options,
+    warnings: &mut Vec<Warning>,
+    output: impl Write,
+) -> Result<(), Vec<CompileError>> {
+    compile_with_dumps(source, options, warnings, output, Dumps::default())
+}
+
+/// 与 [`compile`] 相同, 同时按 dumps 输出各阶段的中间结果. 出错之前完成的阶段的结果仍会输出
+pub fn compile_with_dumps(
+    source: &str,
+    options: &Options,
+    warnings: &mut Vec<Warning>,
+    output: impl Write,
+    dumps: Dumps<'_>,
+) -> Result<(), Vec<CompileError>> {
+    let (comp_unit, syntax_errors) = parse_syntax(source)?;
+    if let Some(dump) = dumps.ast {
+        writeln!(dump, "{:#?}", comp_unit).map_err(|err| vec![err.into()])?;
+    }
+    let checked_unit = check(comp_unit, syntax_errors, warnings)?;
+    if let Some(dump) = dumps.symbols {
+        dump.write_all(checked_unit.dump_symbols().as_bytes()).map_err(|err| vec![err.into()])?;
+    }
+    let mut program = generate_ir(&checked_unit);
+    optimizer::optimize(&mut program, options.opt_level);
+    emit(&program, options.emit, output, dumps.ir, dumps.asm).map_err(|err| vec![err])
+}
+
+/// 输出 IR 或汇编, 以及要求输出的中间结果
+fn emit(
+    program: &Program,
+    emit: Emit,
+    output: impl Write,
+    ir_dump: Option<&mut dyn Write>,
+    asm_dump: Option<&mut dyn Write>,
+) -> Result<(), CompileError> {
+    if let Some(dump) = ir_dump {
+        generate_koopa(program, dump)?;
+    }
+    if let Some(dump) = asm_dump {
+        generate_riscv_asm(program, dump)?;
+    }
+    match emit {
+        Emit::Koopa => Ok(generate_koopa(program, output)?),
+        Emit::Riscv => generate_riscv_asm(program, output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //返回所有错误的信息
+    fn error_messages(source: &str) -> Vec<String> {
+        parse(source).unwrap_err().iter().map(CompileError::message).collect()
+    }
+
+    //返回每个错误诊断信息的第一行
+    fn error_lines(source: &str) -> Vec<String> {
+        let errors = parse(source).unwrap_err();
+        errors.iter().map(|err| err.report("t.c", source).lines().next().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn syntax_errors_in_several_items_are_all_reported() {
+        let source = "int f() { int a = 1 return a; }\nint g( { }\nint main() { x = ; return 0 }\n";
+        assert_eq!(
+            error_lines(source),
+            [
+                "t.c:1:21: syntax error: expected an operator, ',' or ';', found 'return'",
+                "t.c:2:8: syntax error: expected ')' or 'int', found '{'",
+                "t.c:3:18: syntax error: expected an expression, found ';'",
+                "t.c:3:29: syntax error: expected ';', found '}'",
+            ]
+        );
+    }
+
+    #[test]
+    fn parsing_resumes_after_a_bad_statement() {
+        let source = "int main() { int a = 1; a = a + ; a = * 2; return a; }";
+        assert_eq!(
+            error_lines(source),
+            [
+                "t.c:1:33: syntax error: expected an expression, found ';'",
+                "t.c:1:39: syntax error: expected an expression, found '*'",
+            ]
+        );
+    }
+
+    #[test]
+    fn recovered_ast_is_still_checked() {
+        //语法错误与恢复后发现的语义错误按位置一起报告
+        let source = "int main() { int a = ; return b; }\nint f( { }\nint g() { return d; }\n";
+        assert_eq!(
+            error_lines(source),
+            [
+                "t.c:1:22: syntax error: expected an expression or '{', found ';'",
+                "t.c:1:31: error: Use of undeclared identifier 'b'",
+                "t.c:2:8: syntax error: expected ')' or 'int', found '{'",
+                "t.c:3:18: error: Use of undeclared identifier 'd'",
+            ]
+        );
+    }
+
+    #[test]
+    fn semantic_errors_are_all_reported() {
+        let source = "int main() { int a; a = b; c(); return d; }";
+        assert_eq!(
+            error_messages(source),
+            [
+                "Use of undeclared identifier 'b'",
+                "Call to undefined function 'c'",
+                "Use of undeclared identifier 'd'",
+            ]
+        );
+    }
+
+    #[test]
+    fn functions_and_globals_share_a_scope() {
+        for source in [
+            "int f = 1; int f() { return 2; } int main() { return 0; }",
+            "int f() { return 2; } const int f = 1; int main() { return 0; }",
+            "int getint[2]; int main() { return 0; }",
+        ] {
+            let messages = error_messages(source);
+            assert_eq!(messages.len(), 1, "{}", source);
+            assert!(messages[0].ends_with("as a different kind of symbol"), "{}", messages[0]);
+        }
+    }
+
+    #[test]
+    fn shadowed_function_cannot_be_called() {
+        assert_eq!(
+            error_messages("int main() { int getint = 1; return getint() + getint; }"),
+            ["Called object 'getint' is not a function"]
+        );
+        //只是遮蔽而不调用是合法的
+        assert!(parse("int f() { return 1; } int main() { int f = 2; return f; }").is_ok());
+    }
+
+    #[test]
+    fn break_and_continue_outside_a_loop_are_errors() {
+        let source = "int main() { break; while (1) { break; } { continue; } return 0; }";
+        assert_eq!(
+            error_lines(source),
+            [
+                "t.c:1:14: error: break statement not within a loop",
+                "t.c:1:44: error: continue statement not within a loop",
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_literals_do_not_stop_parsing() {
+        let messages = error_messages("int main() { int a = 4294967296; int b = 0x100000000; return a + b; }");
+        assert_eq!(
+            messages,
+            [
+                "integer literal is too large to be represented in 32 bits",
+                "integer literal is too large to be represented in 32 bits",
+            ]
+        );
+    }
+}
@@ -1,51 +1,104 @@
 
-pub mod ast;
-pub mod ir_builder;
-pub mod asm_builder;
-use koopa::back::KoopaGenerator;
-use ir_builder::generate_ir;
-use lalrpop_util::lalrpop_mod;
+//! 命令行入口, 编译的各个阶段都在库中 (见 lib.rs).
+
+mod cli;
+use compiler_pku::{compile_with_dumps, Dumps};
+use cli::{Command, DumpTarget};
 use std::env::args;
-use std::fs::read_to_string;
-use std::io::Result;
-// 引用 lalrpop 生成的解析器
-// 因为我们刚刚创建了 sysy.lalrpop, 所以模块名是 sysy
-lalrpop_mod!(sysy);
+use std::io::{Read, Write};
 
-fn main() -> Result<()> {
+fn main() {
   // 解析命令行参数
-  let mut args = args();
-  args.next();
-  let mode = args.next().unwrap();
-  let input = args.next().unwrap();
-  args.next();
-  let output = args.next().unwrap();
-  //println!("{}",mode);
-  // 读取输入文件
-  let input = read_to_string(input)?;
-
-  // 调用 lalrpop 生成的 parser 解析输入文件
-  let ast = sysy::CompUnitParser::new().parse(&input).unwrap();
-
-  // 输出解析得到的 AST
-  println!("{:#?}", ast);
-  //生成IR
-  let ir: koopa::ir::Program = generate_ir(&ast).expect("IR builder error");
-  //匹配运行模式
-  match mode.as_str() {
-      "-koopa" =>{ //生成koopa
-        println!("now generate koopa");
-        let mut text_generator = KoopaGenerator::new(Vec::new());
-        text_generator.generate_on(&ir).unwrap();
-        std::fs::write(output, text_generator.writer()).expect("Unable to write");
-      }
-      "-riscv"=>{ //生成riscv汇编
-        println!("now generate riscv-asm code");
-        let asm_output=std::fs::File::create(output)?;
-        asm_builder::generate_riscv_asm(&ir,asm_output).expect("generate asm error");
-      }
-      _=>unreachable!()
+  let args = match cli::parse_args(args().skip(1)) {
+    Ok(Command::Compile(args)) => args,
+    Ok(Command::Help) => {
+      println!("{}", cli::usage());
+      return;
+    }
+    Ok(Command::Version) => {
+      println!("{} {}", cli::NAME, cli::VERSION);
+      return;
+    }
+    Err(message) => {
+      eprintln!("error: {}", message);
+      eprintln!("Try '{} --help' for more information.", cli::NAME);
+      std::process::exit(1);
+    }
+  };
+  let warning_options = &args.warning_options;
+  // 读取输入文件, "-" 表示标准输入
+  let (file_name, input) = if args.input == "-" {
+    let mut input = String::new();
+    if let Err(err) = std::io::stdin().read_to_string(&mut input) {
+      fail(&format!("cannot read standard input: {}", err));
+    }
+    ("<stdin>".to_string(), input)
+  } else {
+    match std::fs::read_to_string(&args.input) {
+      Ok(input) => (args.input.clone(), input),
+      Err(err) => fail(&format!("cannot read '{}': {}", args.input, err)),
+    }
+  };
+
+  // 编译, 各阶段的中间结果先写到内存中, 最后按 --dump-xxx 选项输出
+  let mut buffers: [Vec<u8>; 4] = Default::default();
+  let [ast_dump, symbols_dump, ir_dump, asm_dump] = &mut buffers;
+  let dumps = Dumps {
+    ast: dump_buffer(&args.dumps.ast, ast_dump),
+    symbols: dump_buffer(&args.dumps.symbols, symbols_dump),
+    ir: dump_buffer(&args.dumps.ir, ir_dump),
+    asm: dump_buffer(&args.dumps.asm, asm_dump),
+  };
+  let mut warnings = Vec::new();
+  let mut output = Vec::new();
+  let result = compile_with_dumps(&input, &args.options, &mut warnings, &mut output, dumps);
+  let targets = [&args.dumps.ast, &args.dumps.symbols, &args.dumps.ir, &args.dumps.asm];
+  for (target, buffer) in targets.into_iter().zip(&buffers) {
+    dump(target, buffer);
+  }
+
+  // 报告所有的警告与错误
+  warnings.retain(|warning| warning_options.is_enabled(warning.kind));
+  for warning in &warnings {
+    eprintln!("{}", warning.report(&file_name, &input, warning_options.werror));
   }
+  if let Err(errors) = result {
+    for err in &errors {
+      eprintln!("{}", err.report(&file_name, &input));
+    }
+    std::process::exit(1);
+  }
+  if warning_options.werror && !warnings.is_empty() {
+    std::process::exit(1);
+  }
+  write_output(&args.output, &output);
+}
+
+// 指定了 --dump-xxx 时, 这一项中间结果写到 buffer 中
+fn dump_buffer<'a>(target: &Option<DumpTarget>, buffer: &'a mut Vec<u8>) -> Option<&'a mut dyn Write> {
+  target.as_ref().map(|_| buffer as &mut dyn Write)
+}
+
+// 按 --dump-xxx 选项输出中间结果, 没有指定时什么也不做
+fn dump(target: &Option<DumpTarget>, content: &[u8]) {
+  let result = match target {
+    None => return,
+    Some(DumpTarget::Stderr) => std::io::stderr().write_all(content),
+    Some(DumpTarget::File(path)) => std::fs::write(path, content),
+  };
+  if let Err(err) = result {
+    fail(&format!("cannot write dump: {}", err));
+  }
+}
+
+fn write_output(path: &str, content: &[u8]) {
+  if let Err(err) = std::fs::write(path, content) {
+    fail(&format!("cannot write '{}': {}", path, err));
+  }
+}
 
-  Ok(())
+// 打印与源代码无关的错误 (如无法读写文件), 并以非零状态退出
+fn fail(message: &str) -> ! {
+  eprintln!("error: {}", message);
+  std::process::exit(1);
 }
@@ -0,0 +1,67 @@
+//! Optimization passes on the Koopa IR.
+//! 在生成汇编之前按优化级别对 IR 做变换, -O0 时不做任何变换.
+
+use std::collections::HashSet;
+
+use koopa::ir::entities::ValueKind;
+use koopa::ir::{BasicBlock, FunctionData, Program};
+
+/// 优化级别. 目前只有一组优化, 命令行中的 -O2 与 -O1 相同
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    #[default]
+    O0,
+    O1,
+}
+
+pub fn optimize(program: &mut Program, opt_level: OptLevel) {
+    if opt_level == OptLevel::O0 {
+        return;
+    }
+    let funcs: Vec<_> = program.func_layout().to_vec();
+    for func in funcs {
+        remove_unreachable_bbs(program.func_mut(func));
+    }
+}
+
+/// 删除从入口出发无法到达的基本块 (如 return 之后的语句所在的块)
+fn remove_unreachable_bbs(func_data: &mut FunctionData) {
+    //运行时库中的函数只有声明, 没有基本块
+    let entry = match func_data.layout().entry_bb() {
+        Some(entry) => entry,
+        None => return,
+    };
+    let mut reachable = HashSet::new();
+    let mut work_list = vec![entry];
+    while let Some(bb) = work_list.pop() {
+        if !reachable.insert(bb) {
+            continue;
+        }
+        work_list.extend(successors(func_data, bb));
+    }
+    let unreachable: Vec<BasicBlock> = func_data
+        .layout()
+        .bbs()
+        .keys()
+        .filter(|bb| !reachable.contains(bb))
+        .copied()
+        .collect();
+    //块中的值只可能在其他不可达的块中使用, 只需要从布局中移除
+    for bb in unreachable {
+        func_data.layout_mut().bbs_mut().remove(&bb);
+    }
+}
+
+/// 基本块结尾的跳转指令的目标
+fn successors(func_data: &FunctionData, bb: BasicBlock) -> Vec<BasicBlock> {
+    let last_inst = func_data
+        .layout()
+        .bbs()
+        .node(&bb)
+        .and_then(|node| node.insts().back_key().copied());
+    match last_inst.map(|inst| func_data.dfg().value(inst).kind()) {
+        Some(ValueKind::Branch(branch)) => vec![branch.true_bb(), branch.false_bb()],
+        Some(ValueKind::Jump(jump)) => vec![jump.target()],
+        _ => vec![],
+    }
+}
@@ -0,0 +1,605 @@
+//! Check a single component of the AST.
+
+use crate::ast::{exp::*, statements::*};
+use crate::error::{CompileError, Span, Warning, WarningKind};
+use super::const_eval::{fold_binary, ConstEval};
+use super::{ExpType, FunctionSignature, SemanticInfo, Symbol};
+
+pub trait Checkable {
+    fn check(&mut self, semantic_info: &mut SemanticInfo) -> Result<(), CompileError>;
+}
+
+/// 检查一个子节点, 出错时记录下来, 继续检查后面的部分
+fn check_and_record(node: &mut dyn Checkable, semantic_info: &mut SemanticInfo) {
+    if let Err(err) = node.check(semantic_info) {
+        semantic_info.errors.push(err);
+    }
+}
+
+fn warn(semantic_info: &mut SemanticInfo, kind: WarningKind, message: String, span: Span) {
+    semantic_info.warnings.push(Warning::new(kind, message, span));
+}
+
+/// 全局的变量、常量与函数处于同一个作用域, 不能同名
+fn check_global_redefinition(name: &str, span: Span, semantic_info: &SemanticInfo) -> Result<(), CompileError> {
+    let is_function = semantic_info.function_table.contains_key(name);
+    if semantic_info.symbol_table.in_global_scope() && is_function {
+        return Err(CompileError::Semantic(format!("Redefinition of '{}' as a different kind of symbol", name), span));
+    }
+    Ok(())
+}
+
+/// 离开作用域, 报告其中从来没有被读过的变量
+fn exit_scope(semantic_info: &mut SemanticInfo) {
+    for (name, span) in semantic_info.symbol_table.exit_scope() {
+        warn(semantic_info, WarningKind::UnusedVariable, format!("Variable '{}' is declared but never read", name), span);
+    }
+}
+
+impl Checkable for CompUnit {
+    fn check(&mut self, semantic_info: &mut SemanticInfo) -> Result<(), CompileError> {
+        for item in &mut self.items {
+            match item {
+                GlobalItem::FuncDef(func_def) => check_and_record(func_def, semantic_info),
+                GlobalItem::Decl(decl) => check_and_record(decl, semantic_info),
+                //语法错误已经报告过, 继续检查其余部分
+                GlobalItem::Error(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Checkable for FuncDef {
+    fn check(&mut self, semantic_info: &mut SemanticInfo) -> Result<(), CompileError> {
+        let return_type = match self.return_type.type_name.as_str() {
+            "int" => ExpType::Int,
+            "void" => ExpType::Void,
+            _ => return Err(CompileError::Semantic("Wrong return type".to_string(), self.return_type.span)),
+        };
+        if let Some(signature) = semantic_info.function_table.get(&self.func_id) {
+            if signature.is_runtime_lib {
+                return Err(CompileError::Semantic(
+                    format!("Redefinition of '{}', which is a function of the SysY runtime library", self.func_id),
+                    self.span,
+                ));
+            }
+            return Err(CompileError::Semantic(
+                format!("Redefinition of function '{}'", self.func_id),
+                self.span,
+            ));
+        }
+        if semantic_info.symbol_table.is_global(&self.func_id) {
+            return Err(CompileError::Semantic(
+                format!("Redefinition of '{}' as a different kind of symbol", self.func_id),
+                self.span,
+            ));
+        }
+        //数组参数退化为指向第一维元素的指针
+        let mut params = Vec::new();
+        for param in &mut self.params {
+            params.push(match param {
+                FuncFParam::FuncFParam(_, _, _) => ExpType::Int,
+                FuncFParam::ArrayParam(_, _, dims, _) => ExpType::Pointer(check_dims(dims, semantic_info)?),
+            });
+        }
+        //先登记函数, 使函数体内可以递归调用
+        semantic_info.function_table.insert(
+            self.func_id.clone(),
+            FunctionSignature { params: params.clone(), return_type: return_type.clone(), is_runtime_lib: false },
+        );
+        semantic_info.curr_func = Some(self.func_id.clone());
+        //参数与函数体最外层的定义处于同一个作用域
+        semantic_info.symbol_table.enter_scope(&self.func_id);
+        for (param, param_type) in self.params.iter_mut().zip(params) {
+            let (FuncFParam::FuncFParam(_, ident, _) | FuncFParam::ArrayParam(_, ident, _, _)) = param;
+            let dims = match param_type {
+                ExpType::Pointer(dims) => [vec![0], dims].concat(),
+                _ => vec![],
+            };
+            if let Err(err) = semantic_info.symbol_table.insert(ident, Symbol::Var(dims)) {
+                semantic_info.errors.push(err);
+            }
+        }
+        let Block::Block(block_items, block_span) = &mut self.block;
+        for block_item in block_items.iter_mut() {
+            check_and_record(block_item, semantic_info);
+        }
+        exit_scope(semantic_info);
+        //main 函数结束时隐式地返回 0, 不需要警告
+        if return_type == ExpType::Int && self.func_id != "main" && !block_returns(block_items) {
+            warn(
+                semantic_info,
+                WarningKind::ReturnType,
+                format!("Control may reach the end of non-void function '{}', which then returns 0", self.func_id),
+                Span::new(block_span.end - 1, block_span.end),
+            );
+        }
+        semantic_info.curr_func = None;
+        Ok(())
+    }
+}
+
+impl Checkable for Block {
+    fn check(&mut self, semantic_info: &mut SemanticInfo) -> Result<(), CompileError> {
+        match self {
+            Block::Block(block_items, _) => {
+                let func = semantic_info.curr_func.as_deref().unwrap();
+                semantic_info.symbol_table.enter_scope(func);
+                for block_item in block_items {
+                    check_and_record(block_item, semantic_info);
+                }
+                exit_scope(semantic_info);
+            }
+        }
+        Ok(())
+    }
+}
+impl Checkable for BlockItem {
+    fn check(&mut self, semantic_info: &mut SemanticInfo) -> Result<(), CompileError> {
+        match self {
+            BlockItem::Decl(decl) => decl.check(semantic_info),
+            BlockItem::Stmt(stmt) => stmt.check(semantic_info),
+        }
+    }
+}
+impl Checkable for Decl {
+    fn check(&mut self, semantic_info: &mut SemanticInfo) -> Result<(), CompileError> {
+        match self {
+            Decl::ConstDecl(ConstDecl::ConstDecl(_, const_defs, _)) => {
+                for const_def in const_defs {
+                    check_and_record(const_def, semantic_info);
+                }
+            }
+            Decl::VarDecl(VarDecl::VarDecl(_, var_defs, _)) => {
+                for var_def in var_defs {
+                    check_and_record(var_def, semantic_info);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+impl Checkable for ConstDef {
+    fn check(&mut self, semantic_info: &mut SemanticInfo) -> Result<(), CompileError> {
+        match self {
+            ConstDef::ConstDef(ident, dims, const_initval, _) => {
+                //初始值有错时仍然定义这个常量, 避免之后的使用再报告未定义
+                if dims.is_empty() {
+                    let value = match const_initval {
+                        ConstInitVal::ConstExp(exp) => fold_const_exp(exp, semantic_info),
+                        ConstInitVal::InitList(_, span) => Err(CompileError::Semantic(
+                            format!("Scalar constant '{}' can not be initialized with a list", ident.content),
+                            *span,
+                        )),
+                    };
+                    check_global_redefinition(&ident.content, ident.span, semantic_info)?;
+                    semantic_info.symbol_table.insert(ident, Symbol::Const(*value.as_ref().unwrap_or(&0)))?;
+                    return value.map(|_| ());
+                }
+                let dims = check_dims(dims, semantic_info)?;
+                let (values, result) = match const_array_values(ident, &dims, const_initval, semantic_info) {
+                    Ok(values) => (values, Ok(())),
+                    Err(err) => (vec![0; dims.iter().product()], Err(err)),
+                };
+                check_global_redefinition(&ident.content, ident.span, semantic_info)?;
+                semantic_info.symbol_table.insert(ident, Symbol::ConstArray(dims, values))?;
+                result
+            }
+        }
+    }
+}
+impl Checkable for VarDef {
+    fn check(&mut self, semantic_info: &mut SemanticInfo) -> Result<(), CompileError> {
+        let (ident, dims, initval) = match self {
+            VarDef::VarDef(ident, dims, initval, _) => (ident, dims, Some(initval)),
+            VarDef::IDENT(ident, dims, _) => (ident, dims, None),
+        };
+        let dims = check_dims(dims, semantic_info)?;
+        //变量在初始值之后才可见
+        let result = match initval {
+            Some(initval) => check_var_init(ident, &dims, initval, semantic_info),
+            None => Ok(()),
+        };
+        check_global_redefinition(&ident.content, ident.span, semantic_info)?;
+        semantic_info.symbol_table.insert_var(ident, dims)?;
+        result
+    }
+}
+impl Checkable for Stmt {
+    fn check(&mut self, semantic_info: &mut SemanticInfo) -> Result<(), CompileError> {
+        match self {
+            Stmt::ReturnStmt(exp, span) => {
+                let func_name = semantic_info.curr_func.clone().unwrap();
+                let return_type = semantic_info.function_table[&func_name].return_type.clone();
+                match (exp, return_type) {
+                    (Some(exp), ExpType::Void) => {
+                        check_exp(exp, semantic_info)?;
+                        Err(CompileError::Semantic(
+                            format!("Void function '{}' should not return a value", func_name),
+                            exp.span(),
+                        ))
+                    }
+                    (None, ExpType::Int) => Err(CompileError::Semantic(
+                        format!("Non-void function '{}' should return a value", func_name),
+                        *span,
+                    )),
+                    (Some(exp), _) => check_int_exp(exp, semantic_info, "Return value"),
+                    (None, _) => Ok(()),
+                }
+            }
+            Stmt::AssignStmt(lval, exp, stmt_span) => {
+                //在折叠常量之前比较左右两边
+                let self_assign = match (&*lval, &*exp) {
+                    (LVal::IDENT(ident), Expr::Var(rhs_ident)) => ident.content == rhs_ident.content,
+                    (LVal::ArrayElem(ident, indices, _), Expr::Index(rhs_ident, rhs_indices, _)) => {
+                        ident.content == rhs_ident.content && same_exps(indices, rhs_indices)
+                    }
+                    _ => false,
+                };
+                let span = lval.span();
+                let (ident, indices) = match lval {
+                    LVal::IDENT(ident) => (ident, &mut [][..]),
+                    LVal::ArrayElem(ident, indices, _) => (ident, &mut indices[..]),
+                };
+                let dims = match semantic_info.symbol_table.resolve(ident)? {
+                    Symbol::Var(dims) => dims.clone(),
+                    Symbol::Const(_) | Symbol::ConstArray(_, _) => {
+                        return Err(CompileError::Semantic(format!("Cannot assign to constant '{}'", ident.content), ident.span));
+                    }
+                };
+                if index_type(ident, &dims, indices, span, semantic_info)? != ExpType::Int {
+                    return Err(CompileError::Semantic(format!("Array '{}' is not assignable", ident.content), span));
+                }
+                check_int_exp(exp, semantic_info, "Assigned value")?;
+                if self_assign {
+                    let message = format!("Explicitly assigning '{}' to itself", ident.content);
+                    warn(semantic_info, WarningKind::SelfAssign, message, *stmt_span);
+                }
+                Ok(())
+            }
+            Stmt::ExpStmt(exp, _) => {
+                //表达式的值被丢弃, 可以是任意类型
+                if let Some(exp) = exp {
+                    check_exp(exp, semantic_info)?;
+                    if !has_side_effect(exp) {
+                        warn(semantic_info, WarningKind::UnusedValue, "Result of expression is unused".to_string(), exp.span());
+                    }
+                }
+                Ok(())
+            }
+            Stmt::BlockStmt(block) => block.check(semantic_info),
+            Stmt::IfStmt(cond, then_stmt, else_stmt, _) => {
+                if let Err(err) = check_int_exp(cond, semantic_info, "Condition") {
+                    semantic_info.errors.push(err);
+                }
+                check_and_record(&mut **then_stmt, semantic_info);
+                if let Some(else_stmt) = else_stmt {
+                    check_and_record(&mut **else_stmt, semantic_info);
+                }
+                Ok(())
+            }
+            Stmt::WhileStmt(cond, body, _) => {
+                match check_int_exp(cond, semantic_info, "Condition") {
+                    //条件是常量时折叠成字面量, 之后据此判断循环是否会结束
+                    Ok(()) => {
+                        if let Ok(value) = cond.eval(&semantic_info.symbol_table) {
+                            *cond = Expr::Lit(value, cond.span());
+                        }
+                    }
+                    Err(err) => semantic_info.errors.push(err),
+                }
+                semantic_info.loop_depth += 1;
+                check_and_record(&mut **body, semantic_info);
+                semantic_info.loop_depth -= 1;
+                Ok(())
+            }
+            Stmt::BreakStmt(span) if semantic_info.loop_depth == 0 => {
+                Err(CompileError::Semantic("break statement not within a loop".to_string(), *span))
+            }
+            Stmt::ContinueStmt(span) if semantic_info.loop_depth == 0 => {
+                Err(CompileError::Semantic("continue statement not within a loop".to_string(), *span))
+            }
+            Stmt::BreakStmt(_) | Stmt::ContinueStmt(_) => Ok(()),
+            Stmt::Error(_) => Ok(()),
+        }
+    }
+}
+
+/// 语句是否在所有路径上都以 return 结束 (或者永远不会结束)
+fn stmt_returns(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::ReturnStmt(_, _) => true,
+        Stmt::BlockStmt(Block::Block(block_items, _)) => block_returns(block_items),
+        Stmt::IfStmt(_, then_stmt, Some(else_stmt), _) => stmt_returns(then_stmt) && stmt_returns(else_stmt),
+        //条件恒为真 (已经折叠成了非零的字面量) 且没有 break 的循环不会结束
+        Stmt::WhileStmt(Expr::Lit(cond, _), body, _) => *cond != 0 && !stmt_breaks(body),
+        _ => false,
+    }
+}
+fn block_returns(block_items: &[BlockItem]) -> bool {
+    block_items.iter().any(|block_item| matches!(block_item, BlockItem::Stmt(stmt) if stmt_returns(stmt)))
+}
+/// 语句中是否有跳出当前循环的 break, 内层循环中的 break 不算
+fn stmt_breaks(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::BreakStmt(_) => true,
+        Stmt::BlockStmt(Block::Block(block_items, _)) => block_items
+            .iter()
+            .any(|block_item| matches!(block_item, BlockItem::Stmt(stmt) if stmt_breaks(stmt))),
+        Stmt::IfStmt(_, then_stmt, else_stmt, _) => {
+            stmt_breaks(then_stmt) || else_stmt.as_ref().is_some_and(|else_stmt| stmt_breaks(else_stmt))
+        }
+        _ => false,
+    }
+}
+
+/// 表达式语句的值被丢弃, 只有函数调用 (包括 a && f() 这样的写法) 才有意义
+fn has_side_effect(exp: &Expr) -> bool {
+    match exp {
+        Expr::Call(_, _, _) => true,
+        Expr::Binary(BinaryOp::And | BinaryOp::Or, _, second_exp, _) => has_side_effect(second_exp),
+        _ => false,
+    }
+}
+
+/// 两个表达式是否一定得到相同的值: 结构相同, 并且不含函数调用
+fn same_exp(first_exp: &Expr, second_exp: &Expr) -> bool {
+    match (first_exp, second_exp) {
+        (Expr::Lit(first, _), Expr::Lit(second, _)) => first == second,
+        (Expr::Var(first), Expr::Var(second)) => first.content == second.content,
+        (Expr::Index(first, first_indices, _), Expr::Index(second, second_indices, _)) => {
+            first.content == second.content && same_exps(first_indices, second_indices)
+        }
+        (Expr::Unary(first_op, first, _), Expr::Unary(second_op, second, _)) => {
+            first_op == second_op && same_exp(first, second)
+        }
+        (Expr::Binary(first_op, first_lhs, first_rhs, _), Expr::Binary(second_op, second_lhs, second_rhs, _)) => {
+            first_op == second_op && same_exp(first_lhs, second_lhs) && same_exp(first_rhs, second_rhs)
+        }
+        _ => false,
+    }
+}
+fn same_exps(first_exps: &[Expr], second_exps: &[Expr]) -> bool {
+    first_exps.len() == second_exps.len() && first_exps.iter().zip(second_exps).all(|(first, second)| same_exp(first, second))
+}
+
+/// 结果恒定的比较: 两边都是常量, 或者两边是同一个表达式
+fn check_compare(op: BinaryOp, first_exp: &Expr, second_exp: &Expr, span: Span, semantic_info: &mut SemanticInfo) {
+    let symbol_table = &semantic_info.symbol_table;
+    let result = match (first_exp.eval(symbol_table), second_exp.eval(symbol_table)) {
+        (Ok(first), Ok(second)) => fold_binary(op, first, second, span).ok(),
+        _ if same_exp(first_exp, second_exp) => Some(matches!(op, BinaryOp::Le | BinaryOp::Ge | BinaryOp::Eq) as i32),
+        _ => None,
+    };
+    if let Some(result) = result {
+        let message = format!("Comparison '{}' is always {}", op, result != 0);
+        warn(semantic_info, WarningKind::TautologicalCompare, message, span);
+    }
+}
+
+/// 检查表达式并求出它的类型, 引用标量常量的地方替换成常量的值
+fn check_exp(exp: &mut Expr, semantic_info: &mut SemanticInfo) -> Result<ExpType, CompileError> {
+    match exp {
+        Expr::Binary(op, first_exp, second_exp, span) => {
+            let context = format!("Operands of '{}'", op);
+            check_int_exp(first_exp, semantic_info, &context)?;
+            check_int_exp(second_exp, semantic_info, &context)?;
+            if matches!(op, BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge | BinaryOp::Eq | BinaryOp::NotEq) {
+                check_compare(*op, first_exp, second_exp, *span, semantic_info);
+            }
+            Ok(ExpType::Int)
+        }
+        Expr::Unary(op, exp, _) => {
+            check_int_exp(exp, semantic_info, &format!("Operand of '{}'", op))?;
+            Ok(ExpType::Int)
+        }
+        Expr::Call(ident, args, span) => {
+            //局部变量可以遮蔽同名的函数, 此时不能再调用它
+            if semantic_info.symbol_table.lookup(ident).is_ok() {
+                return Err(CompileError::Semantic(format!("Called object '{}' is not a function", ident.content), ident.span));
+            }
+            let signature = match semantic_info.function_table.get(&ident.content) {
+                Some(signature) => signature.clone(),
+                None => return Err(CompileError::Semantic(format!("Call to undefined function '{}'", ident.content), ident.span)),
+            };
+            if signature.params.len() != args.len() {
+                return Err(CompileError::Semantic(
+                    format!(
+                        "Function '{}' expects {} argument(s), but {} were given",
+                        ident.content,
+                        signature.params.len(),
+                        args.len()
+                    ),
+                    *span,
+                ));
+            }
+            for (i, (arg, param_type)) in args.iter_mut().zip(&signature.params).enumerate() {
+                let arg_type = check_exp(arg, semantic_info)?;
+                if arg_type != *param_type {
+                    return Err(CompileError::Semantic(
+                        format!(
+                            "Argument {} of function '{}' has type {}, but {} is expected",
+                            i + 1, ident.content, arg_type, param_type
+                        ),
+                        arg.span(),
+                    ));
+                }
+            }
+            Ok(signature.return_type)
+        }
+        Expr::Var(ident) => {
+            let dims = match semantic_info.symbol_table.resolve(ident)? {
+                Symbol::Var(dims) | Symbol::ConstArray(dims, _) => dims.clone(),
+                Symbol::Const(value) => {
+                    *exp = Expr::Lit(*value, ident.span);
+                    return Ok(ExpType::Int);
+                }
+            };
+            semantic_info.symbol_table.mark_read(ident);
+            Ok(element_type(&dims, 0))
+        }
+        Expr::Index(ident, indices, span) => {
+            let dims = match semantic_info.symbol_table.resolve(ident)? {
+                Symbol::Var(dims) | Symbol::ConstArray(dims, _) => dims.clone(),
+                Symbol::Const(_) => {
+                    return Err(CompileError::Semantic(format!("Subscripted value '{}' is not an array", ident.content), ident.span))
+                }
+            };
+            semantic_info.symbol_table.mark_read(ident);
+            index_type(ident, &dims, indices, *span, semantic_info)
+        }
+        Expr::Lit(_, _) => Ok(ExpType::Int),
+    }
+}
+
+/// 检查表达式, 它的值必须是 int
+fn check_int_exp(exp: &mut Expr, semantic_info: &mut SemanticInfo, context: &str) -> Result<(), CompileError> {
+    let exp_type = check_exp(exp, semantic_info)?;
+    if exp_type != ExpType::Int {
+        return Err(CompileError::Semantic(format!("{} must be int, not {}", context, exp_type), exp.span()));
+    }
+    Ok(())
+}
+
+/// 变量取若干个下标后的类型
+fn index_type(
+    ident: &IDENT,
+    dims: &[usize],
+    indices: &mut [Expr],
+    span: Span,
+    semantic_info: &mut SemanticInfo,
+) -> Result<ExpType, CompileError> {
+    if indices.len() > dims.len() {
+        return Err(CompileError::Semantic(format!("Subscripted value '{}' is not an array", ident.content), span));
+    }
+    for index in indices.iter_mut() {
+        check_int_exp(index, semantic_info, "Array index")?;
+    }
+    Ok(element_type(dims, indices.len()))
+}
+
+/// 取遍所有维度时得到 int, 否则剩下的子数组退化为指向它第一个元素的指针
+fn element_type(dims: &[usize], index_count: usize) -> ExpType {
+    if index_count == dims.len() {
+        ExpType::Int
+    } else {
+        ExpType::Pointer(dims[index_count + 1..].to_vec())
+    }
+}
+
+/// 计算数组每一维的长度
+fn check_dims(dims: &mut [ConstExp], semantic_info: &SemanticInfo) -> Result<Vec<usize>, CompileError> {
+    let mut lens = Vec::new();
+    for dim in dims {
+        let len = fold_const_exp(dim, semantic_info)?;
+        if len <= 0 {
+            return Err(CompileError::Semantic(format!("Array size must be positive, but got {}", len), dim.span()));
+        }
+        lens.push(len as usize);
+    }
+    Ok(lens)
+}
+
+/// 在编译期求出常量表达式的值, 并把表达式替换成值的字面量
+fn fold_const_exp(const_exp: &mut ConstExp, semantic_info: &SemanticInfo) -> Result<i32, CompileError> {
+    match const_exp {
+        ConstExp::Exp(exp) => fold_exp(exp, semantic_info),
+    }
+}
+fn fold_exp(exp: &mut Expr, semantic_info: &SemanticInfo) -> Result<i32, CompileError> {
+    let value = exp.eval(&semantic_info.symbol_table)?;
+    *exp = Expr::Lit(value, exp.span());
+    Ok(value)
+}
+
+/// 求出常量数组按行优先展开的元素值
+fn const_array_values(
+    ident: &IDENT,
+    dims: &[usize],
+    const_initval: &mut ConstInitVal,
+    semantic_info: &SemanticInfo,
+) -> Result<Vec<i32>, CompileError> {
+    let (list, list_span) = match const_initval {
+        ConstInitVal::InitList(list, span) => (list, *span),
+        ConstInitVal::ConstExp(exp) => {
+            return Err(CompileError::Semantic(
+                format!("Array '{}' must be initialized with a list", ident.content),
+                exp.span(),
+            ))
+        }
+    };
+    fold_const_init_list(list, semantic_info)?;
+    let mut flattened = Vec::new();
+    flatten_init_list(list, dims, &mut flattened)
+        .map_err(|msg| CompileError::Semantic(msg, list_span))?;
+    let mut values = Vec::new();
+    for elem in flattened {
+        values.push(match elem {
+            Some(ConstInitVal::ConstExp(exp)) => exp.eval(&semantic_info.symbol_table)?,
+            _ => 0,
+        });
+    }
+    Ok(values)
+}
+fn fold_const_init_list(list: &mut [ConstInitVal], semantic_info: &SemanticInfo) -> Result<(), CompileError> {
+    for item in list {
+        match item {
+            ConstInitVal::ConstExp(exp) => {
+                fold_const_exp(exp, semantic_info)?;
+            }
+            ConstInitVal::InitList(sub_list, _) => fold_const_init_list(sub_list, semantic_info)?,
+        }
+    }
+    Ok(())
+}
+
+/// 检查变量的初始值; 全局变量的初始值必须是常量表达式
+fn check_var_init(
+    ident: &IDENT,
+    dims: &[usize],
+    initval: &mut InitVal,
+    semantic_info: &mut SemanticInfo,
+) -> Result<(), CompileError> {
+    match initval {
+        InitVal::Exp(exp) if dims.is_empty() => check_init_exp(ident, exp, semantic_info),
+        InitVal::Exp(exp) => Err(CompileError::Semantic(
+            format!("Array '{}' must be initialized with a list", ident.content),
+            exp.span(),
+        )),
+        InitVal::InitList(_, span) if dims.is_empty() => Err(CompileError::Semantic(
+            format!("Scalar variable '{}' can not be initialized with a list", ident.content),
+            *span,
+        )),
+        InitVal::InitList(list, span) => {
+            let mut flattened = Vec::new();
+            flatten_init_list(list, dims, &mut flattened)
+                .map_err(|msg| CompileError::Semantic(msg, *span))?;
+            check_init_list(ident, list, semantic_info)
+        }
+    }
+}
+fn check_init_list(ident: &IDENT, list: &mut [InitVal], semantic_info: &mut SemanticInfo) -> Result<(), CompileError> {
+    for item in list {
+        match item {
+            InitVal::Exp(exp) => check_init_exp(ident, exp, semantic_info)?,
+            InitVal::InitList(sub_list, _) => check_init_list(ident, sub_list, semantic_info)?,
+        }
+    }
+    Ok(())
+}
+fn check_init_exp(ident: &IDENT, exp: &mut Expr, semantic_info: &mut SemanticInfo) -> Result<(), CompileError> {
+    if semantic_info.curr_func.is_some() {
+        return check_int_exp(exp, semantic_info, "Initializer");
+    }
+    fold_exp(exp, semantic_info).map(|_| ()).map_err(|err| {
+        CompileError::Semantic(
+            format!(
+                "Initializer of global variable '{}' is not a constant expression: {}",
+                ident.content,
+                err.message()
+            ),
+            err.span(),
+        )
+    })
+}
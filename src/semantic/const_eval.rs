@@ -0,0 +1,174 @@
+//! Evaluate constant expressions at compile time.
+//! 常量表达式只依赖符号表中的常量, 不会生成任何 IR.
+
+use crate::ast::{exp::*, statements::*};
+use crate::error::{CompileError, Span};
+use super::{Symbol, SymbolTable};
+
+pub trait ConstEval {
+    fn eval(&self, symbol_table: &SymbolTable) -> Result<i32, CompileError>;
+}
+
+impl ConstEval for ConstExp {
+    fn eval(&self, symbol_table: &SymbolTable) -> Result<i32, CompileError> {
+        match self {
+            ConstExp::Exp(exp) => exp.eval(symbol_table),
+        }
+    }
+}
+impl ConstEval for Expr {
+    fn eval(&self, symbol_table: &SymbolTable) -> Result<i32, CompileError> {
+        match self {
+            //与运行时一样短路, 右边不会被求值时其中的除以 0 不算错误
+            Expr::Binary(BinaryOp::And, first_exp, second_exp, _) => {
+                if first_exp.eval(symbol_table)? == 0 {
+                    return Ok(0);
+                }
+                Ok((second_exp.eval(symbol_table)? != 0) as i32)
+            }
+            Expr::Binary(BinaryOp::Or, first_exp, second_exp, _) => {
+                if first_exp.eval(symbol_table)? != 0 {
+                    return Ok(1);
+                }
+                Ok((second_exp.eval(symbol_table)? != 0) as i32)
+            }
+            Expr::Binary(op, first_exp, second_exp, span) => {
+                let lhs = first_exp.eval(symbol_table)?;
+                let rhs = second_exp.eval(symbol_table)?;
+                fold_binary(*op, lhs, rhs, *span)
+            }
+            Expr::Unary(UnaryOp::Plus, exp, _) => exp.eval(symbol_table),
+            Expr::Unary(UnaryOp::Minus, exp, _) => Ok(exp.eval(symbol_table)?.wrapping_neg()),
+            Expr::Unary(UnaryOp::Not, exp, _) => Ok((exp.eval(symbol_table)? == 0) as i32),
+            Expr::Call(_, _, span) => Err(CompileError::Semantic(
+                "Function call should not exist in const expression! ".to_string(),
+                *span,
+            )),
+            Expr::Lit(int, _) => Ok(*int),
+            Expr::Var(ident) => match symbol_table.get(ident)? {
+                Symbol::Const(val) => Ok(*val),
+                Symbol::Var(_) => Err(CompileError::Semantic(
+                    "Left Value should not exist in const expression! ".to_string(),
+                    ident.span,
+                )),
+                Symbol::ConstArray(_, _) => Err(CompileError::Semantic(
+                    format!("Array '{}' can not be used as a value", ident.content),
+                    ident.span,
+                )),
+            },
+            //常量表达式中只能访问常量数组的元素
+            Expr::Index(ident, indices, span) => {
+                let (dims, values) = match symbol_table.get(ident)? {
+                    Symbol::ConstArray(dims, values) => (dims, values),
+                    Symbol::Var(_) => {
+                        return Err(CompileError::Semantic(
+                            "Left Value should not exist in const expression! ".to_string(),
+                            ident.span,
+                        ))
+                    }
+                    Symbol::Const(_) => {
+                        return Err(CompileError::Semantic(
+                            format!("Subscripted value '{}' is not an array", ident.content),
+                            ident.span,
+                        ))
+                    }
+                };
+                if indices.len() > dims.len() {
+                    return Err(CompileError::Semantic(
+                        format!("Subscripted value '{}' is not an array", ident.content),
+                        *span,
+                    ));
+                }
+                if indices.len() != dims.len() {
+                    return Err(CompileError::Semantic(
+                        format!("Array '{}' can not be used as a value", ident.content),
+                        *span,
+                    ));
+                }
+                let mut flat_index = 0;
+                for (index_exp, dim) in indices.iter().zip(dims) {
+                    let index = index_exp.eval(symbol_table)?;
+                    if index < 0 || index as usize >= *dim {
+                        return Err(CompileError::Semantic(
+                            format!("Array index {} is out of bounds for '{}'", index, ident.content),
+                            index_exp.span(),
+                        ));
+                    }
+                    flat_index = flat_index * dim + index as usize;
+                }
+                Ok(values[flat_index])
+            }
+        }
+    }
+}
+
+/// 在编译期计算二元运算, 结果与 RISC-V 上的 32 位运算一致: 溢出时回绕, INT_MIN / -1 得到 INT_MIN.
+/// 常量表达式中除以 0 是编译错误.
+pub fn fold_binary(binary_op: BinaryOp, lhs: i32, rhs: i32, span: Span) -> Result<i32, CompileError> {
+    if rhs == 0 && binary_op == BinaryOp::Div {
+        return Err(CompileError::Semantic("Division by zero in constant expression".to_string(), span));
+    }
+    if rhs == 0 && binary_op == BinaryOp::Mod {
+        return Err(CompileError::Semantic("Modulo by zero in constant expression".to_string(), span));
+    }
+    Ok(match binary_op {
+        BinaryOp::NotEq => (lhs != rhs) as i32,
+        BinaryOp::Eq => (lhs == rhs) as i32,
+        BinaryOp::Gt => (lhs > rhs) as i32,
+        BinaryOp::Lt => (lhs < rhs) as i32,
+        BinaryOp::Ge => (lhs >= rhs) as i32,
+        BinaryOp::Le => (lhs <= rhs) as i32,
+        BinaryOp::And => (lhs != 0 && rhs != 0) as i32,
+        BinaryOp::Or => (lhs != 0 || rhs != 0) as i32,
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Div => lhs.wrapping_div(rhs),
+        BinaryOp::Mod => lhs.wrapping_rem(rhs),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold(binary_op: BinaryOp, lhs: i32, rhs: i32) -> Result<i32, CompileError> {
+        fold_binary(binary_op, lhs, rhs, Span::new(3, 8))
+    }
+
+    #[test]
+    fn arithmetic_wraps() {
+        assert_eq!(fold(BinaryOp::Add, i32::MAX, 1).unwrap(), i32::MIN);
+        assert_eq!(fold(BinaryOp::Sub, i32::MIN, 1).unwrap(), i32::MAX);
+        assert_eq!(fold(BinaryOp::Mul, 65536, 65536).unwrap(), 0);
+        assert_eq!(fold(BinaryOp::Div, i32::MIN, -1).unwrap(), i32::MIN);
+        assert_eq!(fold(BinaryOp::Mod, i32::MIN, -1).unwrap(), 0);
+    }
+
+    #[test]
+    fn division_truncates_toward_zero() {
+        assert_eq!(fold(BinaryOp::Div, -7, 2).unwrap(), -3);
+        assert_eq!(fold(BinaryOp::Mod, -7, 2).unwrap(), -1);
+        assert_eq!(fold(BinaryOp::Mod, 7, -2).unwrap(), 1);
+    }
+
+    #[test]
+    fn comparisons_and_logic_give_0_or_1() {
+        assert_eq!(fold(BinaryOp::Lt, -1, 0).unwrap(), 1);
+        assert_eq!(fold(BinaryOp::Ge, -1, 0).unwrap(), 0);
+        assert_eq!(fold(BinaryOp::And, 5, -3).unwrap(), 1);
+        assert_eq!(fold(BinaryOp::Or, 0, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        for (binary_op, message) in [
+            (BinaryOp::Div, "Division by zero in constant expression"),
+            (BinaryOp::Mod, "Modulo by zero in constant expression"),
+        ] {
+            let err = fold(binary_op, 1, 0).unwrap_err();
+            assert_eq!(err.message(), message);
+            assert_eq!(err.span(), Span::new(3, 8));
+        }
+    }
+}
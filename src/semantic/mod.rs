@@ -0,0 +1,280 @@
+//! Semantic analysis of the AST.
+//! 在生成 IR 之前检查名字、类型、常量与返回值, 并一次报告所有的语义错误.
+//! 检查的同时把常量表达式折叠成字面量, 因此 IR 生成时可以假定输入是合法的.
+
+mod checker;
+mod const_eval;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::statements::*;
+use crate::error::{CompileError, Span, Warning};
+use checker::Checkable;
+
+/// 通过了语义检查的 AST: 名字都已解析到定义, 常量表达式都已折叠成字面量, 可以直接生成 IR.
+/// 只能由 [`crate::parse`] 等入口得到, 其中不含语法错误
+#[derive(Debug)]
+pub struct CheckedUnit {
+    comp_unit: CompUnit,
+    symbol_table: SymbolTable,
+    function_table: HashMap<String, FunctionSignature>,
+}
+impl CheckedUnit {
+    pub fn comp_unit(&self) -> &CompUnit {
+        &self.comp_unit
+    }
+    /// 语义分析得到的符号表: 按定义顺序列出函数的签名, 以及每个作用域中定义的符号
+    pub fn dump_symbols(&self) -> String {
+        let mut dump = String::from("functions:\n");
+        for item in &self.comp_unit.items {
+            if let GlobalItem::FuncDef(func_def) = item {
+                let signature = &self.function_table[&func_def.func_id];
+                let params: Vec<String> = signature.params.iter().map(ExpType::to_string).collect();
+                dump += &format!("  {} {}({})\n", signature.return_type, func_def.func_id, params.join(", "));
+            }
+        }
+        dump + &self.symbol_table.dump()
+    }
+}
+
+/// 发现的警告追加到 warnings 中, 有错误时也会记录.
+/// AST 中的 Error 节点 (已经报告过的语法错误) 被跳过, 调用者需要保证这时不使用得到的结果生成 IR
+pub(crate) fn analyze(mut comp_unit: CompUnit, warnings: &mut Vec<Warning>) -> Result<CheckedUnit, Vec<CompileError>> {
+    let mut semantic_info = SemanticInfo {
+        symbol_table: SymbolTable::new(),
+        function_table: runtime_lib()
+            .into_iter()
+            .map(|(name, signature)| (name.to_string(), signature))
+            .collect(),
+        curr_func: None,
+        loop_depth: 0,
+        errors: Vec::new(),
+        warnings: Vec::new(),
+    };
+    if let Err(err) = comp_unit.check(&mut semantic_info) {
+        semantic_info.errors.push(err);
+    }
+    //未使用的变量在离开作用域时才报告, 按位置重新排序
+    semantic_info.warnings.sort_by_key(|warning| warning.span.start);
+    warnings.append(&mut semantic_info.warnings);
+    if semantic_info.errors.is_empty() {
+        Ok(CheckedUnit {
+            comp_unit,
+            symbol_table: semantic_info.symbol_table,
+            function_table: semantic_info.function_table,
+        })
+    } else {
+        Err(semantic_info.errors)
+    }
+}
+
+/// SysY 运行时库 (libsysy) 中的函数, IR 生成时也据此声明这些函数
+pub fn runtime_lib() -> Vec<(&'static str, FunctionSignature)> {
+    let int_ptr = ExpType::Pointer(vec![]);
+    let decls = [
+        ("getint", vec![], ExpType::Int),
+        ("getch", vec![], ExpType::Int),
+        ("getarray", vec![int_ptr.clone()], ExpType::Int),
+        ("putint", vec![ExpType::Int], ExpType::Void),
+        ("putch", vec![ExpType::Int], ExpType::Void),
+        ("putarray", vec![ExpType::Int, int_ptr], ExpType::Void),
+        ("starttime", vec![], ExpType::Void),
+        ("stoptime", vec![], ExpType::Void),
+    ];
+    decls
+        .into_iter()
+        .map(|(name, params, return_type)| (name, FunctionSignature { params, return_type, is_runtime_lib: true }))
+        .collect()
+}
+
+pub struct SemanticInfo {
+    symbol_table: SymbolTable,                        //符号表
+    function_table: HashMap<String, FunctionSignature>, //已定义的函数
+    curr_func: Option<String>,                        //当前所在的函数, 全局作用域中为 None
+    loop_depth: usize,                                //当前所在的循环层数
+    errors: Vec<CompileError>,                        //已经发现的错误
+    warnings: Vec<Warning>,                           //已经发现的警告
+}
+
+/// 表达式的类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpType {
+    Int,
+    Void,
+    /// 指向数组元素的指针, 记录元素各维的长度, 指向 int 时为空
+    Pointer(Vec<usize>),
+}
+impl fmt::Display for ExpType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpType::Int => write!(f, "int"),
+            ExpType::Void => write!(f, "void"),
+            ExpType::Pointer(dims) if dims.is_empty() => write!(f, "int *"),
+            ExpType::Pointer(dims) => {
+                write!(f, "int (*)")?;
+                for dim in dims {
+                    write!(f, "[{}]", dim)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionSignature {
+    pub params: Vec<ExpType>,
+    pub return_type: ExpType,
+    pub is_runtime_lib: bool,
+}
+
+#[derive(Debug)]
+pub enum Symbol {
+    /// 变量, 记录各维的长度, 标量为空; 数组参数第一维的长度未知, 记为 0
+    Var(Vec<usize>),
+    Const(i32),
+    ConstArray(Vec<usize>, Vec<i32>), //常量数组: 各维长度, 按行优先展开的元素值
+}
+impl Symbol {
+    //用类似声明的形式描述名为 name 的符号, 如 int a[][3], const int N = 10
+    fn describe(&self, name: &str) -> String {
+        let dims_text = |dims: &[usize]| -> String {
+            dims.iter()
+                .map(|&dim| if dim == 0 { "[]".to_string() } else { format!("[{}]", dim) })
+                .collect()
+        };
+        match self {
+            Symbol::Var(dims) => format!("int {}{}", name, dims_text(dims)),
+            Symbol::Const(value) => format!("const int {} = {}", name, value),
+            Symbol::ConstArray(dims, values) => {
+                let values: Vec<String> = values.iter().map(i32::to_string).collect();
+                format!("const int {}{} = {{{}}}", name, dims_text(dims), values.join(", "))
+            }
+        }
+    }
+}
+
+/// 符号表: 记录所有定义过的符号与作用域, 并按作用域嵌套查找名字
+#[derive(Debug)]
+pub struct SymbolTable {
+    symbols: Vec<SymbolEntry>,  //所有的定义, 下标即 SymbolId
+    scope_infos: Vec<ScopeInfo>, //所有出现过的作用域, 下标即作用域的编号, 0 是全局作用域
+    scopes: Vec<Scope>,         //当前打开的作用域, 最内层在栈顶, 栈底是全局作用域
+}
+#[derive(Debug)]
+struct SymbolEntry {
+    name: String,
+    symbol: Symbol,
+    scope: usize,         //定义所在的作用域
+    unread: Option<Span>, //还没有被读过的局部变量的定义位置, 用于报告未使用的变量
+}
+#[derive(Debug)]
+struct ScopeInfo {
+    parent: Option<usize>,
+    func: Option<String>, //作用域所在的函数, 全局作用域为 None
+}
+#[derive(Debug)]
+struct Scope {
+    index: usize,
+    names: HashMap<String, SymbolId>,
+}
+impl SymbolTable {
+    fn new() -> Self {
+        SymbolTable {
+            symbols: Vec::new(),
+            scope_infos: vec![ScopeInfo { parent: None, func: None }],
+            scopes: vec![Scope { index: 0, names: HashMap::new() }],
+        }
+    }
+    //在函数 func 中进入一个新的块
+    fn enter_scope(&mut self, func: &str) {
+        let index = self.scope_infos.len();
+        let parent = self.scopes.last().map(|scope| scope.index);
+        self.scope_infos.push(ScopeInfo { parent, func: Some(func.to_string()) });
+        self.scopes.push(Scope { index, names: HashMap::new() });
+    }
+    //离开当前块, 块内定义的符号随之失效; 返回块内从来没有被读过的变量, 按定义的顺序排列
+    fn exit_scope(&mut self) -> Vec<(String, Span)> {
+        let mut unread: Vec<(String, Span)> = self
+            .scopes
+            .pop()
+            .unwrap()
+            .names
+            .into_values()
+            .filter_map(|id| {
+                let entry = &self.symbols[id.0];
+                entry.unread.map(|span| (entry.name.clone(), span))
+            })
+            .collect();
+        unread.sort_by_key(|(_, span)| span.start);
+        unread
+    }
+    //在当前作用域中定义符号, 同一作用域内不允许重复定义; 把分配的编号记录到标识符上
+    fn insert(&mut self, ident: &mut IDENT, symbol: Symbol) -> Result<(), CompileError> {
+        self.insert_entry(ident, symbol, None)
+    }
+    //定义一个变量, 离开作用域之前没有被读过时会报告
+    fn insert_var(&mut self, ident: &mut IDENT, dims: Vec<usize>) -> Result<(), CompileError> {
+        let span = ident.span;
+        self.insert_entry(ident, Symbol::Var(dims), Some(span))
+    }
+    fn insert_entry(&mut self, ident: &mut IDENT, symbol: Symbol, unread: Option<Span>) -> Result<(), CompileError> {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.names.contains_key(&ident.content) {
+            return Err(CompileError::Semantic(format!("Redefinition of '{}'", ident.content), ident.span));
+        }
+        let id = SymbolId(self.symbols.len());
+        self.symbols.push(SymbolEntry { name: ident.content.clone(), symbol, scope: scope.index, unread });
+        scope.names.insert(ident.content.clone(), id);
+        ident.symbol = Some(id);
+        Ok(())
+    }
+    //全局作用域中是否已经定义了这个名字
+    fn is_global(&self, name: &str) -> bool {
+        self.scopes[0].names.contains_key(name)
+    }
+    fn in_global_scope(&self) -> bool {
+        self.scopes.len() == 1
+    }
+    //由内向外查找符号, 内层的定义会遮蔽外层的同名定义
+    fn lookup(&self, ident: &IDENT) -> Result<SymbolId, CompileError> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.names.get(&ident.content))
+            .copied()
+            .ok_or(CompileError::Semantic(
+                format!("Use of undeclared identifier '{}'", ident.content),
+                ident.span,
+            ))
+    }
+    fn get(&self, ident: &IDENT) -> Result<&Symbol, CompileError> {
+        Ok(&self.symbols[self.lookup(ident)?.0].symbol)
+    }
+    //查找符号, 并把找到的定义记录到标识符上, 之后生成 IR 时直接使用
+    fn resolve(&self, ident: &mut IDENT) -> Result<&Symbol, CompileError> {
+        let id = self.lookup(ident)?;
+        ident.symbol = Some(id);
+        Ok(&self.symbols[id.0].symbol)
+    }
+    //记录符号被读过 (作为右值, 或作为数组传给函数)
+    fn mark_read(&mut self, ident: &IDENT) {
+        if let Some(id) = ident.symbol {
+            self.symbols[id.0].unread = None;
+        }
+    }
+    //按作用域列出所有的定义, 每个定义前是它的编号
+    fn dump(&self) -> String {
+        let mut dump = String::new();
+        for (index, info) in self.scope_infos.iter().enumerate() {
+            dump += &match (&info.func, info.parent) {
+                (Some(func), Some(parent)) => format!("scope {} (in '{}', parent {}):\n", index, func, parent),
+                _ => format!("scope {} (global):\n", index),
+            };
+            for (id, entry) in self.symbols.iter().enumerate().filter(|(_, entry)| entry.scope == index) {
+                dump += &format!("  #{} {}\n", id, entry.symbol.describe(&entry.name));
+            }
+        }
+        dump
+    }
+}
@@ -0,0 +1,153 @@
+//! 端到端测试: 通过库的公开接口把 SysY 源代码编译成 Koopa IR 与 RISC-V 汇编
+
+use compiler_pku::optimizer::OptLevel;
+use compiler_pku::{compile, compile_with_dumps, Dumps, Emit, Options};
+
+const SOURCE: &str = "\
+int add(int a, int b) {
+  return a + b;
+}
+
+int main() {
+  int x = 1;
+  if (x) {
+    x = add(x, 2);
+  }
+  return x;
+}
+";
+
+fn compile_to_string(source: &str, options: &Options) -> String {
+    let mut warnings = Vec::new();
+    let mut output = Vec::new();
+    compile(source, options, &mut warnings, &mut output).unwrap();
+    assert!(warnings.is_empty(), "unexpected warnings: {:?}", warnings);
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn emits_koopa_ir() {
+    let koopa = compile_to_string(SOURCE, &Options { emit: Emit::Koopa, ..Default::default() });
+    assert!(koopa.starts_with("decl @getint(): i32\n"));
+    let functions = &koopa[koopa.find("fun @add").unwrap()..];
+    assert_eq!(
+        functions,
+        "\
+fun @add(@a: i32, @b: i32): i32 {
+%entry:
+  @a_1 = alloc i32
+  store @a, @a_1
+  @b_1 = alloc i32
+  store @b, @b_1
+  %0 = load @a_1
+  %1 = load @b_1
+  %2 = add %0, %1
+  ret %2
+}
+
+fun @main(): i32 {
+%entry:
+  @x_1 = alloc i32
+  store 1, @x_1
+  %3 = load @x_1
+  br %3, %then_0, %end_0
+
+%then_0:
+  %4 = load @x_1
+  %5 = call @add(%4, 2)
+  store %5, @x_1
+  jump %end_0
+
+%end_0:
+  %6 = load @x_1
+  ret %6
+}
+"
+    );
+}
+
+#[test]
+fn emits_riscv_asm() {
+    let asm = compile_to_string(SOURCE, &Options::default());
+    let lines: Vec<&str> = asm.lines().collect();
+    //按顺序出现的关键指令: 函数标签、参数传递、调用与返回
+    let expected = [
+        "  .text",
+        "  .global add",
+        "add:",
+        "  add   t0,t0,t1",
+        "  ret",
+        "  .global main",
+        "main:",
+        "  sw    ra, 28(sp)",
+        "  bnez  t0, .Lmain_then_0",
+        "  li    a1, 2",
+        "  call  add",
+        "  lw    ra, 28(sp)",
+        "  ret",
+    ];
+    let mut rest = lines.iter();
+    for line in expected {
+        assert!(rest.any(|found| *found == line), "missing '{}' in:\n{}", line, asm);
+    }
+}
+
+#[test]
+fn o1_removes_unreachable_blocks() {
+    //两个分支都返回, if 之后的块无法到达
+    let source = "int main() { int x = getint(); if (x) { return 1; } else { return 2; } return 3; }";
+    let koopa = |opt_level| compile_to_string(source, &Options { emit: Emit::Koopa, opt_level });
+    assert!(koopa(OptLevel::O0).contains("%end_0:\n  ret 3\n"));
+    assert!(!koopa(OptLevel::O1).contains("ret 3"));
+}
+
+#[test]
+fn reports_errors_and_warnings() {
+    let mut warnings = Vec::new();
+    let mut output = Vec::new();
+    let source = "int main() { int unused; return missing; }";
+    let errors = compile(source, &Options::default(), &mut warnings, &mut output).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message(), "Use of undeclared identifier 'missing'");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].message, "Variable 'unused' is declared but never read");
+    assert!(output.is_empty());
+}
+
+#[test]
+fn writes_requested_dumps() {
+    let (mut symbols, mut ir, mut asm) = (Vec::new(), Vec::new(), Vec::new());
+    let dumps = Dumps { symbols: Some(&mut symbols), ir: Some(&mut ir), asm: Some(&mut asm), ..Default::default() };
+    let mut output = Vec::new();
+    let options = Options { emit: Emit::Koopa, ..Default::default() };
+    compile_with_dumps(SOURCE, &options, &mut Vec::new(), &mut output, dumps).unwrap();
+    let symbols = String::from_utf8(symbols).unwrap();
+    assert!(symbols.starts_with("functions:\n  int add(int, int)\n  int main()\n"), "{}", symbols);
+    assert!(symbols.contains("scope 2 (in 'main', parent 0):\n  #2 int x\n"), "{}", symbols);
+    assert_eq!(ir, output);
+    assert!(String::from_utf8(asm).unwrap().contains("call  add"));
+}
+
+#[test]
+fn global_names_are_kept_as_labels() {
+    //全局变量 x 不能被改名成与函数 x_1 冲突的名字
+    let source = "int x; int x_1() { return 1; } int main() { x = 2; return x_1() + x; }";
+    let asm = compile_to_string(source, &Options::default());
+    let mut labels: Vec<&str> = asm.lines().filter(|line| line.ends_with(':')).collect();
+    assert!(labels.contains(&"x:") && labels.contains(&"x_1:"), "{}", asm);
+    let count = labels.len();
+    labels.sort();
+    labels.dedup();
+    assert_eq!(labels.len(), count, "duplicate labels in:\n{}", asm);
+    assert_eq!(asm.matches("  .global x_1\n").count(), 1);
+}
+
+#[test]
+fn locals_and_params_avoid_global_names() {
+    let source = "int f(int a) { int x = a; return x; } int a; int x_1 = 3; int main() { int x = 1; return f(a) + x; }";
+    let koopa = compile_to_string(source, &Options { emit: Emit::Koopa, ..Default::default() });
+    assert!(koopa.contains("global @a = alloc i32, zeroinit"), "{}", koopa);
+    assert!(koopa.contains("global @x_1 = alloc i32, 3"), "{}", koopa);
+    assert!(koopa.contains("fun @f(@a_1: i32): i32"), "{}", koopa);
+    assert!(koopa.contains("@x_2 = alloc i32"), "{}", koopa);
+}